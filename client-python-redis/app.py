@@ -13,12 +13,16 @@ from typing import Tuple, Sequence
 class Run(exports.Run):
     def run(self):
         args = sys.argv[1:]
-        if len(args) != 1:
-            print(f"usage: {sys.argv[0]} <address>:<port>", file=sys.stderr)
+        if len(args) == 1:
+            asyncio.run(send_and_receive(args[0], 2))
+        elif len(args) == 2 and args[1] == "resp3":
+            asyncio.run(send_and_receive(args[0], 3))
+        elif len(args) == 2 and args[1] == "pubsub":
+            asyncio.run(pubsub(args[0]))
+        else:
+            print(f"usage: {sys.argv[0]} <address>:<port> [resp3|pubsub]", file=sys.stderr)
             exit(-1)
 
-        asyncio.run(send_and_receive(args[0]))
-
 async def resolve(address_and_port: str) -> Tuple[Sequence[IPv4Address | IPv6Address], int]:
     host, separator, port = address_and_port.rpartition(':')
     assert separator
@@ -35,20 +39,73 @@ async def resolve(address_and_port: str) -> Tuple[Sequence[IPv4Address | IPv6Add
         addresses = socket.getaddrinfo(host, None)
         return (list(map(lambda tuple: ipaddress.ip_address(tuple[4][0]), addresses)), int(port))
         
-async def send_and_receive(address: str):
+async def send_and_receive(address: str, protocol: int):
     addresses, port = await resolve(address)
 
+    error = None
     for address in addresses:
+        # With `protocol=3`, `redis-py` sends `HELLO 3` and checks the reply when it connects; otherwise it expects
+        # RESP2 replies throughout (e.g. `$-1` rather than `_` for the missing key below).
+        client = redis.Redis(host=str(address), port=port, protocol=protocol)
         try:
-            client = redis.Redis(host=str(address), port=port)
-
-            await client.set("foo", b"bar")
-            assert await client.get("foo") == b"bar"
-        
+            await client.ping()
+        except (redis.ConnectionError, OSError) as e:
+            error = e
             await client.aclose()
-            return
-        except:
-            pass
+            continue
+
+        await client.set("foo", b"bar")
+        assert await client.get("foo") == b"bar"
+        assert await client.get("missing") is None
+
+        await client.aclose()
+        return
+
+    # Chain the last failure so that e.g. a rejected `HELLO` reply is reported rather than just the addresses.
+    raise Exception(f"unable to connect to {addresses}") from error
+
+async def pubsub(address: str):
+    addresses, port = await resolve(address)
+
+    # Use RESP3 so that messages arrive as push frames rather than replies
+    client = redis.Redis(host=str(addresses[0]), port=port, protocol=3)
+    pubsub = client.pubsub()
+
+    await pubsub.subscribe("news", "weather")
+
+    expected = {b"news": [b"from guest"], b"weather": [b"sunny", b"windy", b"rain"]}
+    received = {channel: [] for channel in expected}
+
+    async def next_message():
+        message = await pubsub.get_message(timeout=10.0)
+        if message is None:
+            raise Exception(f"timed out waiting for messages; received {received}")
+        if message["type"] == "subscribe":
+            return message
+
+        channel = message["channel"]
+        assert message["type"] == "message"
+        assert channel in expected, f"unexpected channel: {channel}"
+        received[channel].append(message["data"])
+        assert received[channel] == expected[channel][:len(received[channel])], \
+            f"unexpected messages on {channel}: {received[channel]}"
+        return message
+
+    # Wait until both subscriptions are confirmed before publishing, so our own message can't race ahead of them.
+    # The host may start publishing to `weather` as soon as it sees that subscription, so messages can arrive here
+    # too.
+    subscribed = set()
+    while subscribed != set(expected):
+        message = await next_message()
+        if message["type"] == "subscribe":
+            subscribed.add(message["channel"])
+
+    # The host publishes to `weather` once it sees our subscription; we publish to `news` ourselves.
+    assert await client.publish("news", "from guest") == 1
+
+    while received != expected:
+        await next_message()
 
-    raise Exception(f"unable to connect to {addresses}")
-    
+    await pubsub.unsubscribe()
+    await pubsub.aclose()
+    await client.aclose()
@@ -2,21 +2,59 @@
 from encodings import idna
 
 import sys
+import time
+import json
 import asyncio
 import socket
 import ipaddress
 from ipaddress import IPv4Address, IPv6Address
 from command import exports
-from typing import Tuple, Sequence
+from command.exports.scenarios import Config
+from command.types import Err
+from typing import List, Tuple, Sequence
+
+SCENARIOS = ["echo", "bench"]
 
 class Run(exports.Run):
     def run(self):
         args = sys.argv[1:]
-        if len(args) != 1:
-            print(f"usage: tcp <address>:<port>", file=sys.stderr)
+        if len(args) == 0 or not scenario(args[0], args[1:]):
+            print(f"usage: tcp <address>:<port> [echo | bench <iterations> <bulk bytes>]", file=sys.stderr)
             exit(-1)
 
-        asyncio.run(send_and_receive(args[0]))
+class Scenarios(exports.Scenarios):
+    def list_scenarios(self) -> List[str]:
+        return SCENARIOS
+
+    def run_scenario(self, name: str, config: Config) -> None:
+        try:
+            ok = scenario(config.address, [name] + config.args)
+        except Exception as e:
+            raise Err(repr(e))
+
+        if not ok:
+            raise Err(f"unknown scenario or bad arguments: {[name] + config.args}")
+
+def scenario(address: str, args: List[str]) -> bool:
+    """Run the scenario named by the first of `args` (or `echo` if there
+    isn't one) against `address`, passing it the rest, returning `False` if
+    the arguments aren't recognized."""
+    if len(args) == 0 or (len(args) == 1 and args[0] == "echo"):
+        asyncio.run(send_and_receive(address))
+    elif len(args) == 3 and args[0] == "bench":
+        try:
+            iterations, bulk_bytes = int(args[1]), int(args[2])
+        except ValueError:
+            return False
+
+        if iterations <= 0:
+            return False
+
+        asyncio.run(bench(address, iterations, bulk_bytes))
+    else:
+        return False
+
+    return True
 
 async def resolve(address_and_port: str) -> Tuple[Sequence[IPv4Address | IPv6Address], int]:
     host, separator, port = address_and_port.rpartition(':')
@@ -56,4 +94,54 @@ async def send_and_receive(address: str):
         return
 
     raise Exception(f"unable to connect to {addresses}")
-    
+
+def median(samples: Sequence[int]) -> int:
+    return sorted(samples)[len(samples) // 2]
+
+async def bench(address: str, iterations: int, bulk_bytes: int):
+    """Measure connect latency, round trip latency, and bulk echo
+    throughput against the echo fixture, printing the results as a single
+    line of JSON."""
+    chunk_size = 16 * 1024
+    addresses, port = await resolve(address)
+    address = str(addresses[0])
+
+    connects = []
+    for _ in range(iterations):
+        start = time.perf_counter_ns()
+        rx, tx = await asyncio.open_connection(address, port)
+        connects.append(time.perf_counter_ns() - start)
+        tx.close()
+        await tx.wait_closed()
+
+    rx, tx = await asyncio.open_connection(address, port)
+
+    message = b"*" * 64
+    round_trips = []
+    for _ in range(iterations):
+        start = time.perf_counter_ns()
+        tx.write(message)
+        await tx.drain()
+        await rx.readexactly(len(message))
+        round_trips.append(time.perf_counter_ns() - start)
+
+    chunk = b"*" * chunk_size
+    start = time.perf_counter_ns()
+    remaining = bulk_bytes
+    while remaining > 0:
+        length = min(remaining, chunk_size)
+        tx.write(chunk[:length])
+        await tx.drain()
+        await rx.readexactly(length)
+        remaining -= length
+    bulk = time.perf_counter_ns() - start
+
+    tx.close()
+    await tx.wait_closed()
+
+    print(json.dumps({
+        "connect_ns": median(connects),
+        "round_trip_ns": median(round_trips),
+        "bulk_bytes": bulk_bytes,
+        "bulk_ns": bulk,
+    }, separators=(",", ":")))
@@ -1,19 +1,143 @@
 #![deny(warnings)]
 
+wit_bindgen::generate!({
+    world: "scenario-runner",
+    path: "../client/wit",
+    exports: {
+        "test:test/scenarios": Scenarios,
+    },
+});
+
 use {
     anyhow::{anyhow, Context, Result},
+    exports::test::test::scenarios::{self, Config},
     std::{
         env,
         io::{Read, Write},
+        iter,
         net::{SocketAddr, TcpStream, ToSocketAddrs},
+        num::NonZeroUsize,
         str::FromStr,
+        time::{Duration, Instant},
     },
 };
 
-fn main() -> Result<()> {
-    let address = &env::args().nth(1).ok_or_else(|| {
-        anyhow!("expected IPv4 or IPv6 socket address or <hostname>:<port> as CLI argument")
-    })?;
+fn echo(addresses: &[SocketAddr]) -> Result<()> {
+    for &address in addresses {
+        if let Ok(mut stream) = TcpStream::connect(address) {
+            let message = b"So rested he by the Tumtum tree";
+            stream.write_all(message)?;
+
+            let mut buffer = vec![0; message.len()];
+            stream.read_exact(&mut buffer)?;
+
+            assert_eq!(message.as_slice(), &buffer);
+
+            return Ok(());
+        }
+    }
+
+    Err(anyhow!("unable to connect to {addresses:?}"))
+}
+
+/// Connect to a link-local address with a scope ID and check that the scope ID is preserved in the peer and
+/// local addresses.  Flow labels aren't reported unless the socket opts in via `IPV6_FLOWINFO_SEND`, so we expect
+/// zero for those.
+fn scope_id(address: SocketAddr) -> Result<()> {
+    let SocketAddr::V6(expected) = address else {
+        return Err(anyhow!("expected an IPv6 address; got {address}"));
+    };
+    if expected.scope_id() == 0 {
+        return Err(anyhow!("expected a scope ID in {address}"));
+    }
+
+    let mut stream = TcpStream::connect(address)?;
+
+    let SocketAddr::V6(peer) = stream.peer_addr()? else {
+        return Err(anyhow!("expected an IPv6 peer address"));
+    };
+    assert_eq!(expected, peer);
+    assert_eq!(0, peer.flowinfo());
+
+    let SocketAddr::V6(local) = stream.local_addr()? else {
+        return Err(anyhow!("expected an IPv6 local address"));
+    };
+    assert_eq!(expected.scope_id(), local.scope_id());
+    assert_eq!(0, local.flowinfo());
+
+    let message = b"So rested he by the Tumtum tree";
+    stream.write_all(message)?;
+
+    let mut buffer = vec![0; message.len()];
+    stream.read_exact(&mut buffer)?;
+
+    assert_eq!(message.as_slice(), &buffer);
+
+    Ok(())
+}
+
+fn median(mut samples: Vec<Duration>) -> Duration {
+    samples.sort();
+    samples[samples.len() / 2]
+}
+
+/// Measure connect latency, round trip latency, and bulk echo throughput against the echo fixture, printing the
+/// results as a single line of JSON.
+fn bench(address: SocketAddr, iterations: usize, bulk_bytes: usize) -> Result<()> {
+    const CHUNK_SIZE: usize = 16 * 1024;
+
+    let mut connects = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let stream = TcpStream::connect(address)?;
+        connects.push(start.elapsed());
+        drop(stream);
+    }
+
+    let mut stream = TcpStream::connect(address)?;
+
+    let message = [42_u8; 64];
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut round_trips = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        stream.write_all(&message)?;
+        stream.read_exact(&mut buffer[..message.len()])?;
+        round_trips.push(start.elapsed());
+    }
+
+    let chunk = vec![42_u8; CHUNK_SIZE];
+    let start = Instant::now();
+    let mut remaining = bulk_bytes;
+    while remaining > 0 {
+        let length = remaining.min(CHUNK_SIZE);
+        stream.write_all(&chunk[..length])?;
+        stream.read_exact(&mut buffer[..length])?;
+        remaining -= length;
+    }
+    let bulk = start.elapsed();
+
+    println!(
+        r#"{{"connect_ns":{},"round_trip_ns":{},"bulk_bytes":{bulk_bytes},"bulk_ns":{}}}"#,
+        median(connects).as_nanos(),
+        median(round_trips).as_nanos(),
+        bulk.as_nanos()
+    );
+
+    Ok(())
+}
+
+const SCENARIOS: &[&str] = &["echo", "scope-id", "bench"];
+
+/// Run the scenario named by the first of `args` (or `echo` if there isn't one) against `address`, passing it the
+/// rest.
+fn scenario(address: &str, args: &[String]) -> Result<()> {
+    let mut args = args.iter();
+    let mode = args.next();
+    let mut arg = |name: &str| {
+        args.next()
+            .ok_or_else(|| anyhow!("expected {name} as CLI argument"))
+    };
 
     let addresses = if let Ok(address) = SocketAddr::from_str(address) {
         vec![address]
@@ -24,19 +148,43 @@ fn main() -> Result<()> {
             .collect::<Vec<_>>()
     };
 
-    for address in addresses {
-        if let Ok(mut stream) = TcpStream::connect(address) {
-            let message = b"So rested he by the Tumtum tree";
-            stream.write_all(message)?;
+    match mode.map(String::as_str).unwrap_or("echo") {
+        "echo" => echo(&addresses),
+        "scope-id" => scope_id(
+            *addresses
+                .first()
+                .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?,
+        ),
+        "bench" => bench(
+            *addresses
+                .first()
+                .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?,
+            arg("iteration count")?.parse::<NonZeroUsize>()?.get(),
+            arg("bulk transfer size")?.parse()?,
+        ),
+        mode => Err(anyhow!("unknown mode: {mode}")),
+    }
+}
 
-            let mut buffer = vec![0; message.len()];
-            stream.read_exact(&mut buffer)?;
+struct Scenarios;
 
-            assert_eq!(message.as_slice(), &buffer);
+impl scenarios::Guest for Scenarios {
+    fn list_scenarios() -> Vec<String> {
+        SCENARIOS.iter().map(|name| name.to_string()).collect()
+    }
 
-            return Ok(());
-        }
+    fn run_scenario(name: String, config: Config) -> Result<(), String> {
+        let args = iter::once(name).chain(config.args).collect::<Vec<_>>();
+
+        scenario(&config.address, &args).map_err(|e| format!("{e:?}"))
     }
+}
+
+fn main() -> Result<()> {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let (address, args) = args.split_first().ok_or_else(|| {
+        anyhow!("expected IPv4 or IPv6 socket address or <hostname>:<port> as CLI argument")
+    })?;
 
-    Err(anyhow!("unable to connect to {address:?}"))
+    scenario(address, args)
 }
@@ -2,19 +2,50 @@
 
 use {
     anyhow::{anyhow, Context, Result},
+    bytes::Bytes,
+    deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod},
+    futures::{channel::mpsc, future, stream, SinkExt, StreamExt, TryStreamExt},
     std::{
+        collections::HashSet,
         env,
         net::{SocketAddr, ToSocketAddrs},
         str::FromStr,
     },
+    tokio_postgres::{
+        error::{DbError, SqlState},
+        AsyncMessage, Client, Config, GenericClient, NoTls,
+    },
 };
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
-    let address = &env::args().nth(1).ok_or_else(|| {
+    let mut args = env::args().skip(1);
+
+    let address = &args.next().ok_or_else(|| {
         anyhow!("expected IPv4 or IPv6 socket address or <hostname>:<port> as CLI argument")
     })?;
 
+    let (mut client, config, notices) = connect(address).await?;
+
+    match args.next().as_deref() {
+        None => query(&client).await,
+        Some("copy") => copy(&client).await,
+        Some("cancel") => cancel(&client, notices).await,
+        Some("transaction") => transaction(&mut client).await,
+        Some("pool") => {
+            let size = args
+                .next()
+                .ok_or_else(|| anyhow!("expected pool size"))?
+                .parse()?;
+            pool(&client, config, size).await
+        }
+        Some(mode) => Err(anyhow!("unknown mode: {mode}")),
+    }
+}
+
+/// Connect to `address`, returning the client along with the configuration which worked and a stream of any notices
+/// the server sends.
+async fn connect(address: &str) -> Result<(Client, Config, mpsc::UnboundedReceiver<DbError>)> {
     let addresses = if let Ok(address) = SocketAddr::from_str(address) {
         vec![address]
     } else {
@@ -28,27 +59,221 @@ async fn main() -> Result<()> {
     };
 
     for address in addresses {
-        if let Ok((client, connection)) = tokio_postgres::Config::new()
+        let mut config = Config::new();
+        config
             .hostaddr(address.ip())
             .port(address.port())
             .user("test")
-            .password("test")
-            .connect(tokio_postgres::NoTls)
-            .await
-        {
+            .password("test");
+
+        if let Ok((client, mut connection)) = config.connect(NoTls).await {
+            let (tx, notices) = mpsc::unbounded();
+            let mut messages = stream::poll_fn(move |cx| connection.poll_message(cx));
             tokio::spawn(async move {
-                if let Err(e) = connection.await {
-                    eprintln!("connection error: {e}");
+                while let Some(message) = messages.next().await {
+                    match message {
+                        Ok(AsyncMessage::Notice(notice)) => _ = tx.unbounded_send(notice),
+                        Ok(_) => {}
+                        Err(e) => {
+                            eprintln!("connection error: {e}");
+                            break;
+                        }
+                    }
                 }
             });
 
-            let rows = client.query("SELECT $1::TEXT", &[&"hello world"]).await?;
+            return Ok((client, config, notices));
+        }
+    }
+
+    Err(anyhow!("unable to connect to {address:?}"))
+}
+
+async fn query(client: &impl GenericClient) -> Result<()> {
+    let rows = client.query("SELECT $1::TEXT", &[&"hello world"]).await?;
+
+    assert_eq!(rows[0].get::<_, &str>(0), "hello world");
+
+    Ok(())
+}
+
+/// Stream a few megabytes to the server with `COPY ... FROM STDIN` and read it back with `COPY ... TO STDOUT`.
+async fn copy(client: &Client) -> Result<()> {
+    const ROWS: usize = 100_000;
+    const CHUNK_SIZE: usize = 8 * 1024;
+
+    let data = (0..ROWS)
+        .map(|row| format!("{row}\trow number {row} of {ROWS}\n"))
+        .collect::<String>();
+
+    let sink = client.copy_in("COPY copy_test FROM STDIN").await?;
+    tokio::pin!(sink);
+    for chunk in data.as_bytes().chunks(CHUNK_SIZE) {
+        sink.send(Bytes::copy_from_slice(chunk)).await?;
+    }
+    let count = sink.as_mut().finish().await?;
+
+    assert_eq!(ROWS as u64, count);
+
+    let received = client
+        .copy_out("COPY copy_test TO STDOUT")
+        .await?
+        .try_fold(Vec::new(), |mut received, chunk| async move {
+            received.extend_from_slice(&chunk);
+            Ok(received)
+        })
+        .await?;
+
+    if received != data.as_bytes() {
+        return Err(anyhow!(
+            "COPY TO STDOUT returned {} bytes which differ from the {} bytes sent",
+            received.len(),
+            data.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Cancel a slow query, which requires `tokio-postgres` to open a second connection to send the cancel request.
+async fn cancel(client: &Client, mut notices: mpsc::UnboundedReceiver<DbError>) -> Result<()> {
+    let token = client.cancel_token();
+
+    // The server ignores cancel requests which arrive while no query is running, so wait for the notice it sends once
+    // the sleep has started.
+    let (result, cancelled) = futures::join!(client.execute("SELECT pg_sleep(60)", &[]), async {
+        let notice = notices
+            .next()
+            .await
+            .ok_or_else(|| anyhow!("connection closed before query started"))?;
+        if notice.message() != "sleeping" {
+            return Err(anyhow!("unexpected notice: {notice:?}"));
+        }
+        token.cancel_query(NoTls).await?;
+        Ok::<_, anyhow::Error>(())
+    });
+    cancelled?;
+
+    match result {
+        Err(e) if e.code() == Some(&SqlState::QUERY_CANCELED) => {}
+        Err(e) => return Err(anyhow!("expected query to be cancelled; got {e:?}")),
+        Ok(_) => return Err(anyhow!("expected query to be cancelled; it succeeded")),
+    }
+
+    // The original connection should still be usable.
+    query(client).await
+}
+
+/// Check that `result` is an error with the specified SQLSTATE code.
+fn expect_error<T>(result: Result<T, tokio_postgres::Error>, expected: &SqlState) -> Result<()> {
+    match result {
+        Err(e) if e.code() == Some(expected) => Ok(()),
+        Err(e) => Err(anyhow!("expected SQLSTATE {}; got {e:?}", expected.code())),
+        Ok(_) => Err(anyhow!(
+            "expected SQLSTATE {}; got success",
+            expected.code()
+        )),
+    }
+}
+
+/// Check error reporting both inside and outside of transaction blocks, plus recovery afterward.
+async fn transaction(client: &mut Client) -> Result<()> {
+    // Outside a transaction block, an error only affects the statement which caused it.
+    expect_error(
+        client.execute("SELECT nonsense", &[]).await,
+        &SqlState::FEATURE_NOT_SUPPORTED,
+    )?;
+    query(client).await?;
+
+    // Inside one, an error causes everything else to fail until the block ends.
+    let transaction = client.transaction().await?;
+    query(&transaction).await?;
+    expect_error(
+        transaction.execute("SELECT nonsense", &[]).await,
+        &SqlState::FEATURE_NOT_SUPPORTED,
+    )?;
+    expect_error(
+        transaction
+            .query("SELECT $1::TEXT", &[&"hello world"])
+            .await,
+        &SqlState::IN_FAILED_SQL_TRANSACTION,
+    )?;
+    transaction.rollback().await?;
+
+    query(client).await?;
+
+    let transaction = client.transaction().await?;
+    query(&transaction).await?;
+    transaction.commit().await?;
+
+    query(client).await
+}
 
-            assert_eq!(rows[0].get::<_, &str>(0), "hello world");
+/// Run concurrent queries through a pool of `size` connections, then have the server drop each of them while idle
+/// and make sure the pool replaces them.
+async fn pool(client: &Client, config: Config, size: usize) -> Result<()> {
+    let manager = Manager::from_config(
+        config,
+        NoTls,
+        ManagerConfig {
+            // Check each connection with a round trip before reusing it, so we notice the ones the server dropped.
+            recycling_method: RecyclingMethod::Verified,
+        },
+    );
+    let pool = Pool::builder(manager).max_size(size).build()?;
 
-            return Ok(());
+    let original = backend_pids(&pool, size).await?;
+
+    for &pid in &original {
+        let terminated = client
+            .query_one("SELECT pg_terminate_backend($1)", &[&pid])
+            .await?
+            .get::<_, bool>(0);
+
+        if !terminated {
+            return Err(anyhow!("unable to terminate backend {pid}"));
         }
     }
 
-    Err(anyhow!("unable to connect to {address:?}"))
+    let replacements = backend_pids(&pool, size).await?;
+
+    if !original.is_disjoint(&replacements) {
+        return Err(anyhow!(
+            "expected new connections after termination; got {original:?} then {replacements:?}"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check out `size` connections from `pool` at once and query each one's backend process ID from its own task.
+async fn backend_pids(pool: &Pool, size: usize) -> Result<HashSet<i32>> {
+    let clients = future::try_join_all((0..size).map(|_| pool.get())).await?;
+
+    let tasks = clients
+        .into_iter()
+        .map(|client| {
+            tokio::spawn(async move {
+                let pid = client
+                    .query_one("SELECT pg_backend_pid()", &[])
+                    .await?
+                    .get::<_, i32>(0);
+                query(&**client).await?;
+                Ok::<_, anyhow::Error>(pid)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut pids = HashSet::new();
+    for task in tasks {
+        pids.insert(task.await??);
+    }
+
+    if pids.len() != size {
+        return Err(anyhow!(
+            "expected {size} distinct connections; got {pids:?}"
+        ));
+    }
+
+    Ok(pids)
 }
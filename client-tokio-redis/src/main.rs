@@ -0,0 +1,105 @@
+#![deny(warnings)]
+
+use {
+    anyhow::{anyhow, Context, Result},
+    futures::StreamExt,
+    redis::{
+        aio::MultiplexedConnection, AsyncCommands, Client, ConnectionAddr, ConnectionInfo,
+        ProtocolVersion, RedisConnectionInfo,
+    },
+    std::{
+        env,
+        net::{SocketAddr, ToSocketAddrs},
+        str::FromStr,
+    },
+};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    let address = &env::args().nth(1).ok_or_else(|| {
+        anyhow!("expected IPv4 or IPv6 socket address or <hostname>:<port> as CLI argument")
+    })?;
+
+    let addresses = if let Ok(address) = SocketAddr::from_str(address) {
+        vec![address]
+    } else {
+        // As in `client-tokio-postgres`, we resolve the name here rather than let `redis` do it, since it would use
+        // `tokio::net::lookup_host`, which needs to spawn a thread.
+        address
+            .to_socket_addrs()
+            .with_context(|| format!("unable to resolve {address:?}"))?
+            .collect::<Vec<_>>()
+    };
+
+    for address in addresses {
+        let client = client(address)?;
+        if let Ok(mut connection) = client.get_multiplexed_async_connection().await {
+            get_set(&mut connection).await?;
+            pipeline(&mut connection).await?;
+            pubsub(&client, &mut connection).await?;
+
+            return Ok(());
+        }
+    }
+
+    Err(anyhow!("unable to connect to {address:?}"))
+}
+
+fn client(address: SocketAddr) -> Result<Client> {
+    Ok(Client::open(ConnectionInfo {
+        addr: ConnectionAddr::Tcp(address.ip().to_string(), address.port()),
+        redis: RedisConnectionInfo {
+            // RESP3 lets pub/sub messages arrive as push frames
+            protocol: ProtocolVersion::RESP3,
+            ..RedisConnectionInfo::default()
+        },
+    })?)
+}
+
+async fn get_set(connection: &mut MultiplexedConnection) -> Result<()> {
+    let () = connection.set("foo", "bar").await?;
+    let value: String = connection.get("foo").await?;
+
+    assert_eq!("bar", value);
+
+    Ok(())
+}
+
+async fn pipeline(connection: &mut MultiplexedConnection) -> Result<()> {
+    let (pong, first, second): (String, String, String) = redis::pipe()
+        .cmd("PING")
+        .set("foo", "bar")
+        .ignore()
+        .get("foo")
+        .get("foo")
+        .query_async(connection)
+        .await?;
+
+    assert_eq!(
+        ("PONG", "bar", "bar"),
+        (pong.as_str(), first.as_str(), second.as_str())
+    );
+
+    Ok(())
+}
+
+async fn pubsub(client: &Client, connection: &mut MultiplexedConnection) -> Result<()> {
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe("news").await?;
+
+    let receivers: usize = connection.publish("news", "from guest").await?;
+    assert_eq!(1, receivers);
+
+    let message = pubsub
+        .on_message()
+        .next()
+        .await
+        .ok_or_else(|| anyhow!("pub/sub connection closed unexpectedly"))?;
+
+    assert_eq!("news", message.get_channel_name());
+    assert_eq!("from guest", message.get_payload::<String>()?);
+
+    pubsub.unsubscribe("news").await?;
+
+    Ok(())
+}
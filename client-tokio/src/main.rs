@@ -1,23 +1,299 @@
 #![deny(warnings)]
 
+wit_bindgen::generate!({
+    world: "scenario-runner",
+    path: "../client/wit",
+    exports: {
+        "test:test/scenarios": Scenarios,
+    },
+});
+
 use {
     anyhow::{anyhow, Context, Result},
+    exports::test::test::scenarios::{self, Config},
+    futures::{SinkExt, StreamExt},
+    http_body_util::{BodyExt, Full},
+    hyper::{
+        body::Bytes,
+        client::conn::http1::{self, SendRequest},
+        header::{HOST, TRANSFER_ENCODING},
+        HeaderMap, Method, Request, StatusCode,
+    },
+    hyper_util::rt::TokioIo,
     std::{
-        env,
+        env, iter,
         net::{SocketAddr, ToSocketAddrs},
+        num::NonZeroUsize,
         str::FromStr,
+        time::{Duration, Instant},
     },
     tokio::{
         io::{AsyncReadExt, AsyncWriteExt},
         net::TcpStream,
+        runtime,
     },
+    tokio_tungstenite::tungstenite::Message,
 };
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<()> {
-    let address = &env::args().nth(1).ok_or_else(|| {
-        anyhow!("expected IPv4 or IPv6 socket address or <hostname>:<port> as CLI argument")
-    })?;
+async fn echo(addresses: &[SocketAddr]) -> Result<()> {
+    for &address in addresses {
+        if let Ok(mut stream) = TcpStream::connect(address).await {
+            let message = b"So rested he by the Tumtum tree";
+            stream.write_all(message).await?;
+
+            let mut buffer = vec![0; message.len()];
+            stream.read_exact(&mut buffer).await?;
+
+            assert_eq!(message.as_slice(), &buffer);
+
+            return Ok(());
+        }
+    }
+
+    Err(anyhow!("unable to connect to {addresses:?}"))
+}
+
+/// Same as the fixture's `pattern`, so we can check what it sends us.
+fn pattern(length: usize) -> Vec<u8> {
+    (0..length).map(|i| (i % 251) as u8).collect()
+}
+
+/// Send a request to the HTTP fixture, checking that it's the `count`th request on this connection.
+async fn request(
+    sender: &mut SendRequest<Full<Bytes>>,
+    address: SocketAddr,
+    method: Method,
+    path: &str,
+    body: Vec<u8>,
+    count: usize,
+) -> Result<(HeaderMap, Bytes)> {
+    sender.ready().await?;
+
+    let response = sender
+        .send_request(
+            Request::builder()
+                .method(method)
+                .uri(path)
+                .header(HOST, address.to_string())
+                .body(Full::new(Bytes::from(body)))?,
+        )
+        .await?;
+
+    if response.status() != StatusCode::OK {
+        return Err(anyhow!(
+            "unexpected status for {path}: {}",
+            response.status()
+        ));
+    }
+
+    let (parts, body) = response.into_parts();
+
+    assert_eq!(
+        Some(count.to_string().as_str()),
+        parts
+            .headers
+            .get("x-request-count")
+            .map(|value| value.to_str())
+            .transpose()?
+    );
+
+    Ok((parts.headers, body.collect().await?.to_bytes()))
+}
+
+/// Make several requests to the HTTP fixture over a single keep-alive connection, covering large, chunked, and
+/// echoed bodies.
+async fn http(address: SocketAddr) -> Result<()> {
+    let stream = TcpStream::connect(address).await?;
+    let (mut sender, connection) = http1::handshake(TokioIo::new(stream)).await?;
+    let connection = tokio::spawn(connection);
+
+    let (_, body) = request(
+        &mut sender,
+        address,
+        Method::GET,
+        "/bytes/1048576",
+        Vec::new(),
+        1,
+    )
+    .await?;
+    assert_eq!(pattern(1024 * 1024), body);
+
+    let (headers, body) = request(
+        &mut sender,
+        address,
+        Method::GET,
+        "/chunked/64/1000",
+        Vec::new(),
+        2,
+    )
+    .await?;
+    assert_eq!(
+        Some("chunked"),
+        headers
+            .get(TRANSFER_ENCODING)
+            .map(|value| value.to_str())
+            .transpose()?
+    );
+    assert_eq!(pattern(1000).repeat(64), body);
+
+    let data = pattern(4 * 1024 * 1024);
+    let (_, body) = request(&mut sender, address, Method::POST, "/echo", data.clone(), 3).await?;
+    assert_eq!(data, body);
+
+    // Dropping the sender tells the connection to shut down cleanly.
+    drop(sender);
+    connection.await??;
+
+    Ok(())
+}
+
+/// Upgrade to WebSocket and exchange text, binary, and ping/pong frames with the fixture, then close cleanly.
+async fn websocket(address: SocketAddr) -> Result<()> {
+    let stream = TcpStream::connect(address).await?;
+    let (mut websocket, _) =
+        tokio_tungstenite::client_async(format!("ws://{address}/"), stream).await?;
+
+    let text = "So rested he by the Tumtum tree".to_owned();
+    let binary = pattern(64 * 1024);
+
+    websocket.send(Message::Text(text.clone())).await?;
+    websocket.send(Message::Binary(binary.clone())).await?;
+    websocket.send(Message::Ping(b"guest".to_vec())).await?;
+
+    // The fixture's messages may arrive in any order relative to the echoes, so we just wait until we've seen
+    // everything we expect.  Its ping is answered by `tungstenite` on our behalf, and the fixture tells us when it
+    // gets the pong.
+    let mut text_echoed = false;
+    let mut binary_echoed = false;
+    let mut ponged = false;
+    let mut pinged = false;
+    let mut pong_reported = false;
+    while !(text_echoed && binary_echoed && ponged && pinged && pong_reported) {
+        match websocket
+            .next()
+            .await
+            .ok_or_else(|| anyhow!("connection closed unexpectedly"))??
+        {
+            Message::Text(message) if message == text => text_echoed = true,
+            Message::Text(message) if message == "pong: fixture" => pong_reported = true,
+            Message::Binary(message) if message == binary => binary_echoed = true,
+            Message::Pong(payload) if payload == b"guest" => ponged = true,
+            Message::Ping(payload) if payload == b"fixture" => pinged = true,
+            message => return Err(anyhow!("unexpected message: {message:?}")),
+        }
+    }
+
+    websocket.close(None).await?;
+
+    // The fixture should acknowledge our close frame, after which the stream ends.
+    while let Some(message) = websocket.next().await {
+        match message? {
+            Message::Close(_) => {}
+            message => return Err(anyhow!("unexpected message after close: {message:?}")),
+        }
+    }
+
+    Ok(())
+}
+
+/// Connect to a link-local address with a scope ID and check that the scope ID is preserved in the peer and
+/// local addresses.  Flow labels aren't reported unless the socket opts in via `IPV6_FLOWINFO_SEND`, so we expect
+/// zero for those.
+async fn scope_id(address: SocketAddr) -> Result<()> {
+    let SocketAddr::V6(expected) = address else {
+        return Err(anyhow!("expected an IPv6 address; got {address}"));
+    };
+    if expected.scope_id() == 0 {
+        return Err(anyhow!("expected a scope ID in {address}"));
+    }
+
+    let mut stream = TcpStream::connect(address).await?;
+
+    let SocketAddr::V6(peer) = stream.peer_addr()? else {
+        return Err(anyhow!("expected an IPv6 peer address"));
+    };
+    assert_eq!(expected, peer);
+    assert_eq!(0, peer.flowinfo());
+
+    let SocketAddr::V6(local) = stream.local_addr()? else {
+        return Err(anyhow!("expected an IPv6 local address"));
+    };
+    assert_eq!(expected.scope_id(), local.scope_id());
+    assert_eq!(0, local.flowinfo());
+
+    let message = b"So rested he by the Tumtum tree";
+    stream.write_all(message).await?;
+
+    let mut buffer = vec![0; message.len()];
+    stream.read_exact(&mut buffer).await?;
+
+    assert_eq!(message.as_slice(), &buffer);
+
+    Ok(())
+}
+
+fn median(mut samples: Vec<Duration>) -> Duration {
+    samples.sort();
+    samples[samples.len() / 2]
+}
+
+/// Measure connect latency, round trip latency, and bulk echo throughput against the echo fixture, printing the
+/// results as a single line of JSON.
+async fn bench(address: SocketAddr, iterations: usize, bulk_bytes: usize) -> Result<()> {
+    const CHUNK_SIZE: usize = 16 * 1024;
+
+    let mut connects = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let stream = TcpStream::connect(address).await?;
+        connects.push(start.elapsed());
+        drop(stream);
+    }
+
+    let mut stream = TcpStream::connect(address).await?;
+
+    let message = [42_u8; 64];
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut round_trips = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        stream.write_all(&message).await?;
+        stream.read_exact(&mut buffer[..message.len()]).await?;
+        round_trips.push(start.elapsed());
+    }
+
+    let chunk = vec![42_u8; CHUNK_SIZE];
+    let start = Instant::now();
+    let mut remaining = bulk_bytes;
+    while remaining > 0 {
+        let length = remaining.min(CHUNK_SIZE);
+        stream.write_all(&chunk[..length]).await?;
+        stream.read_exact(&mut buffer[..length]).await?;
+        remaining -= length;
+    }
+    let bulk = start.elapsed();
+
+    println!(
+        r#"{{"connect_ns":{},"round_trip_ns":{},"bulk_bytes":{bulk_bytes},"bulk_ns":{}}}"#,
+        median(connects).as_nanos(),
+        median(round_trips).as_nanos(),
+        bulk.as_nanos()
+    );
+
+    Ok(())
+}
+
+const SCENARIOS: &[&str] = &["echo", "http", "scope-id", "websocket", "bench"];
+
+/// Run the scenario named by the first of `args` (or `echo` if there isn't one) against `address`, passing it the
+/// rest.
+async fn scenario(address: &str, args: &[String]) -> Result<()> {
+    let mut args = args.iter();
+    let mode = args.next();
+    let mut arg = |name: &str| {
+        args.next()
+            .ok_or_else(|| anyhow!("expected {name} as CLI argument"))
+    };
 
     let addresses = if let Ok(address) = SocketAddr::from_str(address) {
         vec![address]
@@ -31,19 +307,71 @@ async fn main() -> Result<()> {
             .collect::<Vec<_>>()
     };
 
-    for address in addresses {
-        if let Ok(mut stream) = TcpStream::connect(address).await {
-            let message = b"So rested he by the Tumtum tree";
-            stream.write_all(message).await?;
+    match mode.map(String::as_str).unwrap_or("echo") {
+        "echo" => echo(&addresses).await,
+        "http" => {
+            http(
+                *addresses
+                    .first()
+                    .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?,
+            )
+            .await
+        }
+        "scope-id" => {
+            scope_id(
+                *addresses
+                    .first()
+                    .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?,
+            )
+            .await
+        }
+        "websocket" => {
+            websocket(
+                *addresses
+                    .first()
+                    .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?,
+            )
+            .await
+        }
+        "bench" => {
+            bench(
+                *addresses
+                    .first()
+                    .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?,
+                arg("iteration count")?.parse::<NonZeroUsize>()?.get(),
+                arg("bulk transfer size")?.parse()?,
+            )
+            .await
+        }
+        mode => Err(anyhow!("unknown mode: {mode}")),
+    }
+}
 
-            let mut buffer = vec![0; message.len()];
-            stream.read_exact(&mut buffer).await?;
+struct Scenarios;
 
-            assert_eq!(message.as_slice(), &buffer);
+impl scenarios::Guest for Scenarios {
+    fn list_scenarios() -> Vec<String> {
+        SCENARIOS.iter().map(|name| name.to_string()).collect()
+    }
 
-            return Ok(());
-        }
+    fn run_scenario(name: String, config: Config) -> Result<(), String> {
+        let args = iter::once(name).chain(config.args).collect::<Vec<_>>();
+
+        runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(anyhow::Error::from)
+            .and_then(|runtime| runtime.block_on(scenario(&config.address, &args)))
+            .map_err(|e| format!("{e:?}"))
     }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let (address, args) = args.split_first().ok_or_else(|| {
+        anyhow!("expected IPv4 or IPv6 socket address or <hostname>:<port> as CLI argument")
+    })?;
 
-    Err(anyhow!("unable to connect to {address:?}"))
+    scenario(address, args).await
 }
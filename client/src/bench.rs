@@ -0,0 +1,87 @@
+use {
+    crate::{
+        connect, resolve,
+        wasi::{
+            io::streams::{InputStream, OutputStream},
+            sockets::network::Network,
+        },
+    },
+    anyhow::{anyhow, Result},
+    std::time::{Duration, Instant},
+};
+
+const CHUNK_SIZE: usize = 16 * 1024;
+
+fn write_all(tx: &OutputStream, mut bytes: &[u8]) -> Result<()> {
+    // `blocking-write-and-flush` accepts at most 4096 bytes per call.
+    while !bytes.is_empty() {
+        let (chunk, rest) = bytes.split_at(bytes.len().min(4096));
+        tx.blocking_write_and_flush(chunk)?;
+        bytes = rest;
+    }
+    Ok(())
+}
+
+fn read_exact(rx: &InputStream, length: usize) -> Result<()> {
+    let mut remaining = length;
+    while remaining > 0 {
+        remaining -= rx.blocking_read(remaining.try_into()?)?.len();
+    }
+    Ok(())
+}
+
+fn median(mut samples: Vec<Duration>) -> Duration {
+    samples.sort();
+    samples[samples.len() / 2]
+}
+
+/// Measure connect latency, round trip latency, and bulk echo throughput against the echo fixture, printing the
+/// results as a single line of JSON.
+pub fn run(network: &Network, address: &str, iterations: usize, bulk_bytes: usize) -> Result<()> {
+    let address = resolve(network, address)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?;
+
+    let mut connects = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let (client, (rx, tx)) = connect(network, address)?;
+        connects.push(start.elapsed());
+        drop((rx, tx));
+        drop(client);
+    }
+
+    let (_client, (rx, tx)) = connect(network, address)?;
+
+    let message = [42_u8; 64];
+    let mut round_trips = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        write_all(&tx, &message)?;
+        read_exact(&rx, message.len())?;
+        round_trips.push(start.elapsed());
+    }
+
+    let chunk = vec![42_u8; CHUNK_SIZE];
+    let start = Instant::now();
+    let mut remaining = bulk_bytes;
+    while remaining > 0 {
+        let length = remaining.min(CHUNK_SIZE);
+        write_all(&tx, &chunk[..length])?;
+        read_exact(&rx, length)?;
+        remaining -= length;
+    }
+    let bulk = start.elapsed();
+
+    drop((rx, tx));
+
+    println!(
+        r#"{{"connect_ns":{},"round_trip_ns":{},"bulk_bytes":{bulk_bytes},"bulk_ns":{}}}"#,
+        median(connects).as_nanos(),
+        median(round_trips).as_nanos(),
+        bulk.as_nanos()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use {
+    crate::{
+        bind, connect_socket, family_of, listen, port, read_line, resolve, to_std, udp_options,
+        wasi::{
+            io::streams::{InputStream, OutputStream},
+            sockets::{
+                network::{
+                    ErrorCode, IpAddressFamily, IpSocketAddress, Ipv4SocketAddress,
+                    Ipv6SocketAddress, Network,
+                },
+                tcp_create_socket, udp_create_socket,
+            },
+        },
+        with_port,
+    },
+    anyhow::{anyhow, Result},
+    std::net::SocketAddr,
+};
+
+/// An address from the documentation ranges, which no interface on the host should have.
+fn non_local(family: IpAddressFamily) -> IpSocketAddress {
+    match family {
+        IpAddressFamily::Ipv6 => IpSocketAddress::Ipv6(Ipv6SocketAddress {
+            address: (0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
+            port: 0,
+            flow_info: 0,
+            scope_id: 0,
+        }),
+        IpAddressFamily::Ipv4 => IpSocketAddress::Ipv4(Ipv4SocketAddress {
+            address: (192, 0, 2, 1),
+            port: 0,
+        }),
+    }
+}
+
+/// Check that the fixture reports our port as `expected`, then make sure it echoes what we send.
+fn check_peer(rx: &InputStream, tx: &OutputStream, expected: u16) -> Result<()> {
+    let peer = read_line(rx)?.parse::<SocketAddr>()?;
+    assert_eq!(expected, peer.port());
+
+    let message = b"So rested he by the Tumtum tree";
+    tx.blocking_write_and_flush(message)?;
+
+    let mut buffer = Vec::with_capacity(message.len());
+    while buffer.len() < message.len() {
+        buffer.extend(rx.blocking_read((message.len() - buffer.len()).try_into()?)?);
+    }
+    assert_eq!(message.as_slice(), &buffer);
+
+    Ok(())
+}
+
+fn check_tcp(network: &Network, fixture: IpSocketAddress, local_port: u16) -> Result<()> {
+    let family = family_of(&fixture);
+
+    // Bind to the port we were given before connecting.
+    let client = tcp_create_socket::create_tcp_socket(family)?;
+    bind(network, &client, with_port(fixture, local_port))?;
+    assert_eq!(local_port, port(client.local_address()?));
+
+    // Once bound, a socket can't be bound again.
+    assert_eq!(
+        Err(ErrorCode::InvalidState),
+        client.start_bind(network, with_port(fixture, 0))
+    );
+
+    let (rx, tx) = connect_socket(network, &client, fixture)?;
+    check_peer(&rx, &tx, local_port)?;
+    drop((rx, tx));
+    drop(client);
+
+    // Binding to port zero should pick an ephemeral port, and that's what the fixture should see.
+    let client = tcp_create_socket::create_tcp_socket(family)?;
+    bind(network, &client, with_port(fixture, 0))?;
+    let ephemeral = port(client.local_address()?);
+    assert_ne!(0, ephemeral);
+
+    let (rx, tx) = connect_socket(network, &client, fixture)?;
+    check_peer(&rx, &tx, ephemeral)?;
+    drop((rx, tx));
+    drop(client);
+
+    // Binding to a port some other socket is listening on should fail.  Note that the host sets `SO_REUSEADDR`
+    // implicitly, so this is only guaranteed when the other socket is listening rather than connected.
+    let listener = tcp_create_socket::create_tcp_socket(family)?;
+    bind(network, &listener, with_port(fixture, 0))?;
+    listen(&listener)?;
+    let taken = port(listener.local_address()?);
+
+    let other = tcp_create_socket::create_tcp_socket(family)?;
+    assert_eq!(
+        Err(ErrorCode::AddressInUse),
+        bind(network, &other, with_port(fixture, taken))
+    );
+
+    // A failed bind may be retried on the same socket.
+    bind(network, &other, with_port(fixture, 0))?;
+
+    let socket = tcp_create_socket::create_tcp_socket(family)?;
+    assert_eq!(
+        Err(ErrorCode::AddressNotBindable),
+        bind(network, &socket, non_local(family))
+    );
+
+    Ok(())
+}
+
+fn check_udp(network: &Network, fixture: IpSocketAddress, local_port: u16) -> Result<()> {
+    let family = family_of(&fixture);
+
+    let socket = udp_create_socket::create_udp_socket(family)?;
+    udp_options::bind(network, &socket, with_port(fixture, local_port))?;
+    assert_eq!(local_port, port(socket.local_address()?));
+
+    let (rx, tx) = socket.stream(Some(fixture))?;
+    let data = b"Long time the manxome foe he sought".to_vec();
+    udp_options::send(&tx, data.clone(), None)?;
+
+    let datagram = udp_options::receive(&rx)?;
+    assert_eq!(to_std(fixture), to_std(datagram.remote_address));
+    assert_eq!(data, datagram.data);
+
+    drop((rx, tx));
+
+    let socket = udp_create_socket::create_udp_socket(family)?;
+    assert_eq!(
+        Err(ErrorCode::AddressNotBindable),
+        udp_options::bind(network, &socket, non_local(family))
+    );
+
+    Ok(())
+}
+
+/// Bind TCP and UDP sockets to `local_port` (which the host has checked is free) and to ephemeral ports before
+/// talking to the fixture, and exercise the `address-in-use` and `address-not-bindable` error paths.
+pub fn run(network: &Network, address: &str, local_port: u16) -> Result<()> {
+    let fixture = resolve(network, address)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?;
+
+    check_tcp(network, fixture, local_port)?;
+    check_udp(network, fixture, local_port)
+}
@@ -0,0 +1,59 @@
+use {
+    crate::{connect, resolve, wasi::sockets::network::Network},
+    anyhow::{anyhow, Result},
+};
+
+/// Connect, then drop the socket, its streams and a pollable for each of those three in the comma-separated `order`
+/// given.
+///
+/// The host is expected to trap as soon as we drop a resource which still has live children, so only orders which
+/// drop each pollable before the resource it came from and the socket after both streams will run to completion.
+pub fn run(network: &Network, address: &str, order: &str) -> Result<()> {
+    let address = resolve(network, address)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?;
+
+    let (socket, (rx, tx)) = connect(network, address)?;
+
+    // Nothing has been sent, so this won't be ready until we drop it.
+    let input_pollable = rx.subscribe();
+    assert!(!input_pollable.ready());
+    let output_pollable = tx.subscribe();
+    let socket_pollable = socket.subscribe();
+
+    let mut socket = Some(socket);
+    let mut input = Some(rx);
+    let mut output = Some(tx);
+    let mut input_pollable = Some(input_pollable);
+    let mut output_pollable = Some(output_pollable);
+    let mut socket_pollable = Some(socket_pollable);
+
+    for name in order.split(',') {
+        let dropped = match name {
+            "socket" => socket.take().map(drop),
+            "input" => input.take().map(drop),
+            "output" => output.take().map(drop),
+            "input-pollable" => input_pollable.take().map(drop),
+            "output-pollable" => output_pollable.take().map(drop),
+            "socket-pollable" => socket_pollable.take().map(drop),
+            _ => return Err(anyhow!("unknown resource: {name:?}")),
+        };
+
+        if dropped.is_none() {
+            return Err(anyhow!("{name} dropped twice in {order:?}"));
+        }
+    }
+
+    if socket.is_some()
+        || input.is_some()
+        || output.is_some()
+        || input_pollable.is_some()
+        || output_pollable.is_some()
+        || socket_pollable.is_some()
+    {
+        return Err(anyhow!("{order:?} does not drop every resource"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use {
+    crate::{
+        bind, connect, listen, read_line, resolve, to_std, unspecified,
+        wasi::sockets::{
+            network::{
+                ErrorCode, IpAddressFamily, IpSocketAddress, Ipv4SocketAddress, Ipv6SocketAddress,
+                Network,
+            },
+            tcp_create_socket,
+        },
+    },
+    anyhow::{anyhow, Result},
+    std::net::SocketAddr,
+};
+
+fn ipv4(address: (u8, u8, u8, u8), port: u16) -> IpSocketAddress {
+    IpSocketAddress::Ipv4(Ipv4SocketAddress { address, port })
+}
+
+fn ipv6(address: (u16, u16, u16, u16, u16, u16, u16, u16), port: u16) -> IpSocketAddress {
+    IpSocketAddress::Ipv6(Ipv6SocketAddress {
+        address,
+        port,
+        flow_info: 0,
+        scope_id: 0,
+    })
+}
+
+/// `::ffff:127.0.0.1`
+fn mapped_localhost(port: u16) -> IpSocketAddress {
+    ipv6((0, 0, 0, 0, 0, 0xffff, 0x7f00, 0x0001), port)
+}
+
+/// Connect to the dual-stack fixture at `address` and check that it sees `expected_peer` (formatted as by Rust's
+/// `SocketAddr`, minus the port) and echoes what we send.
+fn check_connection(
+    network: &Network,
+    address: IpSocketAddress,
+    expected_peer: &str,
+) -> Result<()> {
+    let (client, (rx, tx)) = connect(network, address)?;
+
+    assert_eq!(to_std(address), to_std(client.remote_address()?));
+
+    let local_port = to_std(client.local_address()?).port();
+    let peer = read_line(&rx)?.parse::<SocketAddr>()?;
+    assert_eq!(expected_peer, peer.ip().to_string());
+    assert_eq!(local_port, peer.port());
+
+    let message = b"So rested he by the Tumtum tree";
+    tx.blocking_write_and_flush(message)?;
+
+    let mut buffer = Vec::with_capacity(message.len());
+    while buffer.len() < message.len() {
+        buffer.extend(rx.blocking_read((message.len() - buffer.len()).try_into()?)?);
+    }
+    assert_eq!(message.as_slice(), &buffer);
+
+    drop((rx, tx));
+
+    Ok(())
+}
+
+fn start_connect(
+    network: &Network,
+    family: IpAddressFamily,
+    address: IpSocketAddress,
+) -> Result<(), ErrorCode> {
+    tcp_create_socket::create_tcp_socket(family)?.start_connect(network, address)
+}
+
+fn start_bind(
+    network: &Network,
+    family: IpAddressFamily,
+    address: IpSocketAddress,
+) -> Result<(), ErrorCode> {
+    tcp_create_socket::create_tcp_socket(family)?.start_bind(network, address)
+}
+
+/// Exercise a fixture listening on `[::]` with `IPV6_V6ONLY` disabled.
+///
+/// Guest sockets, by contrast, are always IPv6-only, and IPv4-mapped IPv6 addresses are rejected with
+/// `invalid-argument` wherever a socket address is accepted, so the only way to reach the fixture over IPv4 is
+/// with an IPv4 socket.
+pub fn run(network: &Network, address: &str) -> Result<()> {
+    let port = to_std(
+        resolve(network, address)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?,
+    )
+    .port();
+
+    // The fixture sees IPv4 clients as IPv4-mapped IPv6 addresses, but that's invisible to us.
+    check_connection(network, ipv4((127, 0, 0, 1), port), "::ffff:127.0.0.1")?;
+    check_connection(network, ipv6((0, 0, 0, 0, 0, 0, 0, 1), port), "::1")?;
+
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        start_connect(network, IpAddressFamily::Ipv6, mapped_localhost(port))
+    );
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        start_connect(network, IpAddressFamily::Ipv4, mapped_localhost(port))
+    );
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        start_bind(network, IpAddressFamily::Ipv6, mapped_localhost(0))
+    );
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        start_bind(network, IpAddressFamily::Ipv4, mapped_localhost(0))
+    );
+
+    // A guest listener on `[::]` only accepts IPv6 connections.
+    let listener = tcp_create_socket::create_tcp_socket(IpAddressFamily::Ipv6)?;
+    bind(network, &listener, unspecified(IpAddressFamily::Ipv6, 0))?;
+    listen(&listener)?;
+    let port = to_std(listener.local_address()?).port();
+
+    match connect(network, ipv4((127, 0, 0, 1), port)) {
+        Err(error) => assert_eq!(
+            Some(&ErrorCode::ConnectionRefused),
+            error.downcast_ref::<ErrorCode>()
+        ),
+        Ok(_) => return Err(anyhow!("IPv4 connection to IPv6-only listener succeeded")),
+    }
+
+    let (_client, (rx, tx)) = connect(network, ipv6((0, 0, 0, 0, 0, 0, 0, 1), port))?;
+    let (server, (server_rx, server_tx)) = loop {
+        match listener.accept() {
+            Err(ErrorCode::WouldBlock) => listener.subscribe().block(),
+            result => break result?,
+        }
+    };
+    assert_eq!(IpAddressFamily::Ipv6, server.address_family());
+
+    drop((server_rx, server_tx, server, rx, tx));
+
+    Ok(())
+}
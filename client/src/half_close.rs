@@ -0,0 +1,72 @@
+use {
+    crate::{
+        connect, family_of, read_to_end, resolve,
+        wasi::{
+            io::streams::StreamError,
+            sockets::{
+                network::{ErrorCode, IpSocketAddress, Network},
+                tcp::ShutdownType,
+                tcp_create_socket,
+            },
+        },
+    },
+    anyhow::{anyhow, Result},
+};
+
+/// We write first, half-close, and expect the fixture to see EOF before it echoes everything back and closes.
+fn client_first(network: &Network, address: IpSocketAddress) -> Result<()> {
+    let (client, (rx, tx)) = connect(network, address)?;
+
+    let message = b"So rested he by the Tumtum tree";
+    tx.blocking_write_and_flush(b"client-first\n")?;
+    tx.blocking_write_and_flush(message)?;
+
+    client.shutdown(ShutdownType::Send)?;
+    // Shutting down is idempotent.
+    client.shutdown(ShutdownType::Send)?;
+
+    // The fixture only echoes the message once it has seen EOF, so getting it back means the half-close reached the
+    // peer.
+    assert_eq!(message.as_slice(), &read_to_end(&rx)?);
+
+    drop((rx, tx));
+
+    Ok(())
+}
+
+/// The fixture writes first and half-closes; we should see EOF while still being able to write our reply.
+fn server_first(network: &Network, address: IpSocketAddress) -> Result<()> {
+    let (client, (rx, tx)) = connect(network, address)?;
+
+    tx.blocking_write_and_flush(b"server-first\n")?;
+
+    assert_eq!(
+        b"And stood awhile in thought".as_slice(),
+        &read_to_end(&rx)?
+    );
+
+    tx.blocking_write_and_flush(b"Long time the manxome foe he sought")?;
+    client.shutdown(ShutdownType::Both)?;
+
+    assert!(matches!(rx.blocking_read(1), Err(StreamError::Closed)));
+
+    drop((rx, tx));
+
+    Ok(())
+}
+
+pub fn run(network: &Network, address: &str) -> Result<()> {
+    let address = resolve(network, address)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?;
+
+    let unconnected = tcp_create_socket::create_tcp_socket(family_of(&address))?;
+    assert_eq!(
+        Err(ErrorCode::InvalidState),
+        unconnected.shutdown(ShutdownType::Both)
+    );
+
+    client_first(network, address)?;
+    server_first(network, address)
+}
@@ -1,12 +1,35 @@
 #![deny(warnings)]
 
-wit_bindgen::generate!("reactor" in "wit");
+wit_bindgen::generate!({
+    world: "reactor",
+    path: "wit",
+    exports: {
+        "test:test/scenarios": Scenarios,
+    },
+});
+
+mod bench;
+mod bind;
+mod drop_order;
+mod dual_stack;
+mod half_close;
+mod reactor;
+mod readiness;
+mod scope_id;
+mod tcp_options;
+mod udp_options;
 
 use {
     anyhow::{anyhow, Context, Result},
-    std::{env, net::SocketAddr, str::FromStr},
+    exports::test::test::scenarios::{self, Config},
+    std::{
+        env, iter,
+        net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+        num::NonZeroUsize,
+        str::FromStr,
+    },
     wasi::{
-        io::streams::{InputStream, OutputStream},
+        io::streams::{InputStream, OutputStream, StreamError},
         sockets::{
             instance_network, ip_name_lookup,
             network::{
@@ -14,34 +37,17 @@ use {
                 Ipv6SocketAddress, Network,
             },
             tcp::TcpSocket,
-            tcp_create_socket,
+            tcp_create_socket, udp_create_socket,
         },
     },
 };
 
 fn resolve(network: &Network, address: &str) -> Result<Vec<IpSocketAddress>> {
     Ok(if let Ok(address) = SocketAddr::from_str(address) {
-        vec![match address {
-            SocketAddr::V6(address) => {
-                let ip = address.ip().segments();
-                IpSocketAddress::Ipv6(Ipv6SocketAddress {
-                    address: (ip[0], ip[1], ip[2], ip[3], ip[4], ip[5], ip[6], ip[7]),
-                    port: address.port(),
-                    flow_info: 0,
-                    scope_id: 0,
-                })
-            }
-            SocketAddr::V4(address) => {
-                let ip = address.ip().octets();
-                IpSocketAddress::Ipv4(Ipv4SocketAddress {
-                    address: (ip[0], ip[1], ip[2], ip[3]),
-                    port: address.port(),
-                })
-            }
-        }]
+        vec![from_std(address)]
     } else {
         let (hostname, port) = address
-            .split_once(':')
+            .rsplit_once(':')
             .and_then(|(h, p)| u16::from_str(p).ok().map(|p| (h, p)))
             .ok_or_else(|| anyhow!("unable to parse {address} as <hostname>:<port>"))?;
 
@@ -57,45 +63,255 @@ fn resolve(network: &Network, address: &str) -> Result<Vec<IpSocketAddress>> {
             IpAddress::Ipv4(address) => IpSocketAddress::Ipv4(Ipv4SocketAddress { address, port }),
         };
 
-        let stream = ip_name_lookup::resolve_addresses(network, hostname).with_context(context)?;
-        let mut addresses = Vec::new();
-        loop {
-            match stream.resolve_next_address() {
-                Ok(Some(address)) => addresses.push(map(address)),
-                Ok(None) => break addresses,
-                Err(ErrorCode::WouldBlock) => stream.subscribe().block(),
-                Err(error) => return Err(anyhow!(error)).with_context(context),
+        lookup_addresses(network, hostname)
+            .map_err(|error| anyhow!(error))
+            .with_context(context)?
+            .into_iter()
+            .map(map)
+            .collect()
+    })
+}
+
+fn lookup_addresses(network: &Network, name: &str) -> Result<Vec<IpAddress>, ErrorCode> {
+    lookup_addresses_counting(network, name, &mut 0)
+}
+
+/// Like `lookup_addresses`, but add the number of times the host answered `would-block` to `would_blocks`.
+fn lookup_addresses_counting(
+    network: &Network,
+    name: &str,
+    would_blocks: &mut usize,
+) -> Result<Vec<IpAddress>, ErrorCode> {
+    let stream = ip_name_lookup::resolve_addresses(network, name)?;
+    let mut addresses = Vec::new();
+    loop {
+        match stream.resolve_next_address() {
+            Ok(Some(address)) => addresses.push(address),
+            Ok(None) => break Ok(addresses),
+            Err(ErrorCode::WouldBlock) => {
+                *would_blocks += 1;
+                stream.subscribe().block()
             }
+            Err(error) => break Err(error),
         }
-    })
+    }
+}
+
+/// Resolve `name` and compare the result with `expected`, which is either a comma-separated list of addresses in
+/// the order the host should return them or the name of the error the lookup should fail with.
+fn lookup(network: &Network, name: &str, expected: &str) -> Result<()> {
+    check_lookup(network, name, expected, false)
+}
+
+/// Like `lookup`, but also require the host to answer `would-block` at least once before the result is ready.
+fn slow_lookup(network: &Network, name: &str, expected: &str) -> Result<()> {
+    check_lookup(network, name, expected, true)
+}
+
+fn check_lookup(network: &Network, name: &str, expected: &str, slow: bool) -> Result<()> {
+    let mut would_blocks = 0;
+    let actual = match lookup_addresses_counting(network, name, &mut would_blocks) {
+        Ok(addresses) => addresses
+            .into_iter()
+            .map(|address| ip_to_std(address).to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        Err(error) => error.name().to_owned(),
+    };
+
+    if actual != expected {
+        Err(anyhow!(
+            "expected {expected:?} for {name:?}; got {actual:?}"
+        ))
+    } else if slow && would_blocks == 0 {
+        Err(anyhow!(
+            "expected lookup of {name:?} to report would-block before completing"
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Like `lookup`, but for `<host>:<port>` strings as accepted by `resolve`, with any failure reported as `error`.
+fn check_resolve(network: &Network, address: &str, expected: &str) -> Result<()> {
+    let actual = match resolve(network, address) {
+        Ok(addresses) => addresses
+            .into_iter()
+            .map(|address| to_std(address).to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        Err(_) => "error".to_owned(),
+    };
+
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "expected {expected:?} for {address:?}; got {actual:?}"
+        ))
+    }
+}
+
+fn ip_to_std(address: IpAddress) -> IpAddr {
+    match address {
+        IpAddress::Ipv6((a, b, c, d, e, f, g, h)) => Ipv6Addr::new(a, b, c, d, e, f, g, h).into(),
+        IpAddress::Ipv4((a, b, c, d)) => Ipv4Addr::new(a, b, c, d).into(),
+    }
+}
+
+fn from_std(address: SocketAddr) -> IpSocketAddress {
+    match address {
+        SocketAddr::V6(address) => {
+            let ip = address.ip().segments();
+            IpSocketAddress::Ipv6(Ipv6SocketAddress {
+                address: (ip[0], ip[1], ip[2], ip[3], ip[4], ip[5], ip[6], ip[7]),
+                port: address.port(),
+                flow_info: address.flowinfo(),
+                scope_id: address.scope_id(),
+            })
+        }
+        SocketAddr::V4(address) => {
+            let ip = address.ip().octets();
+            IpSocketAddress::Ipv4(Ipv4SocketAddress {
+                address: (ip[0], ip[1], ip[2], ip[3]),
+                port: address.port(),
+            })
+        }
+    }
+}
+
+fn to_std(address: IpSocketAddress) -> SocketAddr {
+    match address {
+        IpSocketAddress::Ipv6(Ipv6SocketAddress {
+            address: (a, b, c, d, e, f, g, h),
+            port,
+            flow_info,
+            scope_id,
+        }) => SocketAddrV6::new(
+            Ipv6Addr::new(a, b, c, d, e, f, g, h),
+            port,
+            flow_info,
+            scope_id,
+        )
+        .into(),
+        IpSocketAddress::Ipv4(Ipv4SocketAddress {
+            address: (a, b, c, d),
+            port,
+        }) => SocketAddrV4::new(Ipv4Addr::new(a, b, c, d), port).into(),
+    }
+}
+
+fn family_of(address: &IpSocketAddress) -> IpAddressFamily {
+    match address {
+        IpSocketAddress::Ipv6(_) => IpAddressFamily::Ipv6,
+        IpSocketAddress::Ipv4(_) => IpAddressFamily::Ipv4,
+    }
+}
+
+fn port(address: IpSocketAddress) -> u16 {
+    match address {
+        IpSocketAddress::Ipv6(address) => address.port,
+        IpSocketAddress::Ipv4(address) => address.port,
+    }
+}
+
+fn with_port(address: IpSocketAddress, port: u16) -> IpSocketAddress {
+    match address {
+        IpSocketAddress::Ipv6(mut address) => {
+            address.port = port;
+            IpSocketAddress::Ipv6(address)
+        }
+        IpSocketAddress::Ipv4(mut address) => {
+            address.port = port;
+            IpSocketAddress::Ipv4(address)
+        }
+    }
+}
+
+fn unspecified(family: IpAddressFamily, port: u16) -> IpSocketAddress {
+    match family {
+        IpAddressFamily::Ipv6 => IpSocketAddress::Ipv6(Ipv6SocketAddress {
+            address: (0, 0, 0, 0, 0, 0, 0, 0),
+            port,
+            flow_info: 0,
+            scope_id: 0,
+        }),
+        IpAddressFamily::Ipv4 => IpSocketAddress::Ipv4(Ipv4SocketAddress {
+            address: (0, 0, 0, 0),
+            port,
+        }),
+    }
+}
+
+fn bind(network: &Network, socket: &TcpSocket, address: IpSocketAddress) -> Result<(), ErrorCode> {
+    socket.start_bind(network, address)?;
+    loop {
+        match socket.finish_bind() {
+            Err(ErrorCode::WouldBlock) => socket.subscribe().block(),
+            result => break result,
+        }
+    }
+}
+
+fn listen(socket: &TcpSocket) -> Result<(), ErrorCode> {
+    socket.start_listen()?;
+    loop {
+        match socket.finish_listen() {
+            Err(ErrorCode::WouldBlock) => socket.subscribe().block(),
+            result => break result,
+        }
+    }
+}
+
+/// Read from `rx` up to and including the next newline, returning the line without its terminator.
+fn read_line(rx: &InputStream) -> Result<String> {
+    let mut line = Vec::new();
+    while line.last() != Some(&b'\n') {
+        line.extend(rx.blocking_read(1)?);
+    }
+    line.pop();
+    Ok(String::from_utf8(line)?)
+}
+
+/// Read from `rx` until the peer closes its end of the stream.
+fn read_to_end(rx: &InputStream) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    loop {
+        match rx.blocking_read(1024) {
+            Ok(bytes) => buffer.extend(bytes),
+            Err(StreamError::Closed) => break Ok(buffer),
+            Err(error) => return Err(anyhow!(error)),
+        }
+    }
 }
 
 fn connect(
     network: &Network,
     address: IpSocketAddress,
 ) -> Result<(TcpSocket, (InputStream, OutputStream))> {
-    let client = tcp_create_socket::create_tcp_socket(match address {
-        IpSocketAddress::Ipv6(_) => IpAddressFamily::Ipv6,
-        IpSocketAddress::Ipv4(_) => IpAddressFamily::Ipv4,
-    })?;
+    let client = tcp_create_socket::create_tcp_socket(family_of(&address))?;
+    let streams = connect_socket(network, &client, address)?;
+    Ok((client, streams))
+}
 
+/// Like `connect`, but using a socket we've already created (and possibly bound).
+fn connect_socket(
+    network: &Network,
+    client: &TcpSocket,
+    address: IpSocketAddress,
+) -> Result<(InputStream, OutputStream)> {
     client.start_connect(network, address)?;
-    Ok(loop {
+    loop {
         match client.finish_connect() {
             Err(ErrorCode::WouldBlock) => client.subscribe().block(),
-            result => break (client, result?),
+            result => break Ok(result?),
         }
-    })
+    }
 }
 
-fn main() -> Result<()> {
-    let address = &env::args().nth(1).ok_or_else(|| {
-        anyhow!("expected IPv4 or IPv6 socket address or <hostname>:<port> as CLI argument")
-    })?;
-
-    let network = instance_network::instance_network();
-    for address in resolve(&network, address)? {
-        if let Ok((_client, (rx, tx))) = connect(&network, address) {
+fn echo(network: &Network, address: &str) -> Result<()> {
+    for address in resolve(network, address)? {
+        if let Ok((_client, (rx, tx))) = connect(network, address) {
             let message = b"So rested he by the Tumtum tree";
             tx.blocking_write_and_flush(message)?;
 
@@ -115,3 +331,142 @@ fn main() -> Result<()> {
 
     Err(anyhow!("unable to connect to {address:?}"))
 }
+
+/// Create sockets until the host refuses, expecting exactly `limit` to succeed.
+fn socket_limit(limit: usize) -> Result<()> {
+    let mut sockets = Vec::new();
+    let error = loop {
+        match tcp_create_socket::create_tcp_socket(IpAddressFamily::Ipv4) {
+            Ok(socket) if sockets.len() < limit => sockets.push(socket),
+            Ok(_) => return Err(anyhow!("host allowed more than {limit} sockets")),
+            Err(error) => break error,
+        }
+    };
+
+    assert_eq!(ErrorCode::NewSocketLimit, error);
+    assert_eq!(limit, sockets.len());
+
+    // The limit applies to sockets created, not sockets alive, and covers UDP as well as TCP.
+    drop(sockets);
+    assert_eq!(
+        Some(ErrorCode::NewSocketLimit),
+        udp_create_socket::create_udp_socket(IpAddressFamily::Ipv4).err()
+    );
+
+    Ok(())
+}
+
+/// Spin forever, expecting the host to interrupt us.
+fn spin() -> Result<()> {
+    let mut count = 0_u64;
+    loop {
+        count = std::hint::black_box(count.wrapping_add(1));
+    }
+}
+
+/// Allocate until the host refuses to grow memory any further.
+fn allocate() -> Result<()> {
+    let mut chunks = Vec::new();
+    loop {
+        chunks.push(std::hint::black_box(vec![1_u8; 1024 * 1024]));
+    }
+}
+
+/// Every mode `scenario` accepts.
+const SCENARIOS: &[&str] = &[
+    "echo",
+    "bind",
+    "reactor",
+    "readiness",
+    "lookup",
+    "slow-lookup",
+    "resolve",
+    "socket-limit",
+    "drop-order",
+    "half-close",
+    "dual-stack",
+    "scope-id",
+    "tcp-options",
+    "udp-options",
+    "bench",
+    "spin",
+    "allocate",
+];
+
+/// Run the scenario named by the first of `args`, passing it the rest.
+fn scenario(network: &Network, address: &str, args: &[String]) -> Result<()> {
+    let (mode, args) = args
+        .split_first()
+        .ok_or_else(|| anyhow!("expected scenario name as CLI argument"))?;
+    let mut args = args.iter();
+    let mut arg = |name: &str| {
+        args.next()
+            .ok_or_else(|| anyhow!("expected {name} as CLI argument"))
+    };
+
+    match mode.as_str() {
+        "echo" => echo(network, address),
+        "bind" => bind::run(network, address, arg("local port")?.parse()?),
+        "readiness" => readiness::run(network, address),
+        "reactor" => reactor::run(network, address, arg("connection count")?.parse()?),
+        "lookup" => lookup(network, arg("name")?, arg("expected result")?),
+        "slow-lookup" => slow_lookup(network, arg("name")?, arg("expected result")?),
+        "resolve" => check_resolve(network, arg("address")?, arg("expected result")?),
+        "socket-limit" => socket_limit(arg("socket limit")?.parse()?),
+        "drop-order" => drop_order::run(network, address, arg("drop order")?),
+        "half-close" => half_close::run(network, address),
+        "dual-stack" => dual_stack::run(network, address),
+        "scope-id" => scope_id::run(network, address),
+        "tcp-options" => tcp_options::run(network, address),
+        "udp-options" => udp_options::run(network, address),
+        "bench" => bench::run(
+            network,
+            address,
+            arg("iteration count")?.parse::<NonZeroUsize>()?.get(),
+            arg("bulk transfer size")?.parse()?,
+        ),
+        "spin" => spin(),
+        "allocate" => allocate(),
+        mode => Err(anyhow!("unknown mode: {mode}")),
+    }
+}
+
+struct Scenarios;
+
+impl scenarios::Guest for Scenarios {
+    fn list_scenarios() -> Vec<String> {
+        SCENARIOS.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn run_scenario(name: String, config: Config) -> Result<(), String> {
+        let args = iter::once(name).chain(config.args).collect::<Vec<_>>();
+
+        scenario(
+            &instance_network::instance_network(),
+            &config.address,
+            &args,
+        )
+        .map_err(|e| format!("{e:?}"))
+    }
+}
+
+fn main() -> Result<()> {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let (address, args) = args.split_first().ok_or_else(|| {
+        anyhow!("expected IPv4 or IPv6 socket address or <hostname>:<port> as CLI argument")
+    })?;
+
+    let network = instance_network::instance_network();
+
+    if args.is_empty() {
+        return echo(&network, address);
+    }
+
+    // Several scenarios may be given, separated by `--`, in which case they all run in this instance, one after
+    // the other.
+    for args in args.split(|arg| arg == "--") {
+        scenario(&network, address, args).with_context(|| args.join(" "))?;
+    }
+
+    Ok(())
+}
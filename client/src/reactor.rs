@@ -0,0 +1,212 @@
+use {
+    crate::{
+        family_of, resolve,
+        wasi::{
+            io::{
+                poll::{self, Pollable},
+                streams::{InputStream, OutputStream},
+            },
+            sockets::{
+                network::{ErrorCode, IpSocketAddress, Network},
+                tcp::TcpSocket,
+                tcp_create_socket,
+            },
+        },
+    },
+    anyhow::{anyhow, Result},
+};
+
+/// A minimal single-threaded reactor: callers register pollables along with a token identifying what's waiting on
+/// them, and `wait` dispatches readiness for all of them from a single `poll` call.
+#[derive(Default)]
+struct Reactor {
+    pending: Vec<(Pollable, usize)>,
+}
+
+impl Reactor {
+    fn register(&mut self, pollable: Pollable, token: usize) {
+        self.pending.push((pollable, token));
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Block until at least one registered pollable is ready, then unregister (and drop) every ready pollable and
+    /// return their tokens.
+    fn wait(&mut self) -> Vec<usize> {
+        let pollables = self
+            .pending
+            .iter()
+            .map(|(pollable, _)| pollable)
+            .collect::<Vec<_>>();
+
+        let mut ready = poll::poll(&pollables);
+        ready.sort_unstable();
+
+        // Remove from the end so earlier indexes stay valid.
+        ready
+            .into_iter()
+            .rev()
+            .map(|index| self.pending.swap_remove(index as usize).1)
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Interest {
+    Connect,
+    Write,
+    Read,
+}
+
+const INTERESTS: usize = 3;
+
+fn token(connection: usize, interest: Interest) -> usize {
+    connection * INTERESTS + interest as usize
+}
+
+fn interest(token: usize) -> (usize, Interest) {
+    let interest = match token % INTERESTS {
+        0 => Interest::Connect,
+        1 => Interest::Write,
+        _ => Interest::Read,
+    };
+    (token / INTERESTS, interest)
+}
+
+/// One echo exchange with the fixture, written and read concurrently so neither side's buffers can fill up and
+/// stall the other.
+struct Connection {
+    streams: Option<(InputStream, OutputStream)>,
+    socket: TcpSocket,
+    message: Vec<u8>,
+    written: usize,
+    received: Vec<u8>,
+}
+
+impl Connection {
+    fn new(network: &Network, address: IpSocketAddress, index: usize, size: usize) -> Result<Self> {
+        let socket = tcp_create_socket::create_tcp_socket(family_of(&address))?;
+        socket.start_connect(network, address)?;
+
+        let prefix = format!("connection {index}: ");
+        let message = prefix
+            .bytes()
+            .chain(b"So rested he by the Tumtum tree. ".iter().copied().cycle())
+            .take(size)
+            .collect();
+
+        Ok(Self {
+            streams: None,
+            socket,
+            message,
+            written: 0,
+            received: Vec::new(),
+        })
+    }
+
+    fn streams(&self) -> &(InputStream, OutputStream) {
+        self.streams.as_ref().unwrap()
+    }
+
+    /// Make whatever progress we can now that the pollable for `interest` is ready, registering interest in
+    /// anything still outstanding.
+    fn ready(&mut self, index: usize, interest: Interest, reactor: &mut Reactor) -> Result<()> {
+        match interest {
+            Interest::Connect => match self.socket.finish_connect() {
+                Ok(streams) => {
+                    self.streams = Some(streams);
+                    let (rx, tx) = self.streams();
+                    reactor.register(tx.subscribe(), token(index, Interest::Write));
+                    reactor.register(rx.subscribe(), token(index, Interest::Read));
+                }
+                Err(ErrorCode::WouldBlock) => {
+                    reactor.register(self.socket.subscribe(), token(index, Interest::Connect))
+                }
+                Err(error) => return Err(anyhow!(error)),
+            },
+
+            Interest::Write => {
+                let (_, tx) = self.streams();
+                let count =
+                    usize::try_from(tx.check_write()?)?.min(self.message.len() - self.written);
+
+                tx.write(&self.message[self.written..][..count])?;
+                self.written += count;
+
+                if self.written < self.message.len() {
+                    reactor.register(tx.subscribe(), token(index, Interest::Write));
+                }
+            }
+
+            Interest::Read => {
+                let (rx, _) = self.streams();
+                let remaining = self.message.len() - self.received.len();
+                let bytes = rx.read(remaining.try_into()?)?;
+                self.received.extend(bytes);
+
+                if self.received.len() < self.message.len() {
+                    reactor.register(rx.subscribe(), token(index, Interest::Read));
+                } else if self.received != self.message {
+                    return Err(anyhow!("connection {index} received garbled echo"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Drive `count` concurrent echo connections to the fixture from a single `poll` loop.
+pub fn run(network: &Network, address: &str, count: usize) -> Result<()> {
+    const MESSAGE_SIZE: usize = 256 * 1024;
+
+    let address = resolve(network, address)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?;
+
+    let mut connections = (0..count)
+        .map(|index| Connection::new(network, address, index, MESSAGE_SIZE))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Declared after `connections` so that any pollables still registered when we return early are dropped before
+    // the streams and sockets they belong to.
+    let mut reactor = Reactor::default();
+
+    for (index, connection) in connections.iter().enumerate() {
+        reactor.register(
+            connection.socket.subscribe(),
+            token(index, Interest::Connect),
+        );
+    }
+
+    let mut polls = 0;
+    let mut most_ready = 0;
+    while !reactor.is_empty() {
+        let ready = reactor.wait();
+        polls += 1;
+        most_ready = most_ready.max(ready.len());
+
+        for token in ready {
+            let (index, interest) = interest(token);
+            connections[index].ready(index, interest, &mut reactor)?;
+        }
+    }
+
+    for (index, connection) in connections.iter().enumerate() {
+        if connection.received != connection.message {
+            return Err(anyhow!("connection {index} did not complete"));
+        }
+    }
+
+    // With several connections in flight, at least one poll should find more than one of them ready; otherwise
+    // we're not really multiplexing.
+    assert!(
+        count < 2 || most_ready > 1,
+        "{count} connections completed in {polls} polls, but never more than one was ready at once"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use {
+    crate::{
+        bind, connect, family_of, listen, read_line, resolve, to_std, unspecified,
+        wasi::{
+            clocks::monotonic_clock,
+            io::{
+                poll::{self, Pollable},
+                streams::{InputStream, OutputStream},
+            },
+            sockets::{
+                network::{IpSocketAddress, Network},
+                tcp::{ShutdownType, TcpSocket},
+                tcp_create_socket,
+            },
+        },
+    },
+    anyhow::{anyhow, Result},
+};
+
+/// How long we're willing to wait for something which shouldn't happen before concluding that it won't.
+const QUIET_PERIOD_NS: u64 = 100_000_000;
+
+/// Give up filling the fixture's receive window after this much data.
+const MAX_UNREAD_BYTES: usize = 64 * 1024 * 1024;
+
+/// Return the indexes `poll` reports as ready, sorted.
+fn ready(pollables: &[&Pollable]) -> Vec<u32> {
+    let mut ready = poll::poll(pollables);
+    ready.sort_unstable();
+    ready
+}
+
+/// Check that `pollable` doesn't become ready within `QUIET_PERIOD_NS`.
+fn stays_pending(pollable: &Pollable) -> bool {
+    let timeout = monotonic_clock::subscribe_duration(QUIET_PERIOD_NS);
+    ready(&[pollable, &timeout]) == [1]
+}
+
+fn command(control: &OutputStream, command: &str) -> Result<()> {
+    control.blocking_write_and_flush(format!("{command}\n").as_bytes())?;
+    Ok(())
+}
+
+/// An input stream should only be ready once the fixture has actually sent something.
+fn check_input(control: &OutputStream, rx: &InputStream) -> Result<()> {
+    let pollable = rx.subscribe();
+    assert!(!pollable.ready());
+    assert!(stays_pending(&pollable));
+
+    command(control, "write 5")?;
+
+    pollable.block();
+    assert!(pollable.ready());
+    drop(pollable);
+
+    let mut received = Vec::new();
+    while received.len() < 5 {
+        received.extend(rx.blocking_read(5 - u64::try_from(received.len())?)?);
+    }
+    assert_eq!(vec![42; 5], received);
+
+    // Reading until nothing's left resets readiness.
+    assert!(rx.read(1)?.is_empty());
+    let pollable = rx.subscribe();
+    assert!(!pollable.ready());
+
+    Ok(())
+}
+
+/// `poll` given a mix of ready and pending pollables should report exactly the ready ones, however many there are.
+fn check_mixed(
+    network: &Network,
+    fixture: IpSocketAddress,
+    control: (&InputStream, &OutputStream),
+    data: (&InputStream, &OutputStream),
+) -> Result<()> {
+    let (control_rx, control_tx) = control;
+    let (data_rx, data_tx) = data;
+
+    command(control_tx, "write 1")?;
+    data_rx.subscribe().block();
+
+    // Nothing will ever connect to this.
+    let listener = tcp_create_socket::create_tcp_socket(family_of(&fixture))?;
+    bind(network, &listener, unspecified(family_of(&fixture), 0))?;
+    listen(&listener)?;
+
+    let data_readable = data_rx.subscribe();
+    let control_readable = control_rx.subscribe();
+    let data_writable = data_tx.subscribe();
+    let incoming = listener.subscribe();
+    let elapsed = monotonic_clock::subscribe_duration(0);
+    let distant = monotonic_clock::subscribe_duration(3_600_000_000_000);
+
+    let pollables = [
+        &data_readable,
+        &control_readable,
+        &data_writable,
+        &incoming,
+        &elapsed,
+        &distant,
+    ];
+    assert_eq!(vec![0, 2, 4], ready(&pollables));
+
+    // Asking again shouldn't change anything, and nor should listing the same pollable more than once.
+    assert_eq!(vec![0, 2, 4], ready(&pollables));
+    assert_eq!(
+        vec![0, 1, 3],
+        ready(&[&data_readable, &data_readable, &incoming, &elapsed])
+    );
+
+    // Lots of ready pollables after a pending one should all be reported.
+    let timers = (0..32)
+        .map(|_| monotonic_clock::subscribe_duration(0))
+        .collect::<Vec<_>>();
+    let pollables = [&distant].into_iter().chain(&timers).collect::<Vec<_>>();
+    assert_eq!((1..=32).collect::<Vec<_>>(), ready(&pollables));
+
+    drop((
+        data_readable,
+        control_readable,
+        data_writable,
+        incoming,
+        elapsed,
+        distant,
+    ));
+
+    assert_eq!(vec![42], data_rx.blocking_read(1)?);
+
+    Ok(())
+}
+
+/// Fill the fixture's receive window while it isn't reading, then check that `check_write` returning zero is
+/// followed by a readiness event once the fixture starts draining.
+fn check_output(
+    control: (&InputStream, &OutputStream),
+    data: (&TcpSocket, &OutputStream),
+) -> Result<()> {
+    let (control_rx, control_tx) = control;
+    let (socket, tx) = data;
+
+    let chunk = vec![0; 64 * 1024];
+    let mut written = 0;
+    loop {
+        let count = usize::try_from(tx.check_write()?)?.min(chunk.len());
+        if count > 0 {
+            tx.write(&chunk[..count])?;
+            written += count;
+        } else {
+            // A zero may just mean an earlier write is still in flight, so we only stop once the stream stays
+            // blocked for a while.
+            let pollable = tx.subscribe();
+            if stays_pending(&pollable) {
+                break;
+            }
+        }
+
+        if written > MAX_UNREAD_BYTES {
+            return Err(anyhow!("wrote {written} bytes without blocking"));
+        }
+    }
+
+    assert_eq!(0, tx.check_write()?);
+
+    command(control_tx, "drain")?;
+
+    let pollable = tx.subscribe();
+    pollable.block();
+    drop(pollable);
+    assert!(tx.check_write()? > 0);
+
+    tx.blocking_flush()?;
+    socket.shutdown(ShutdownType::Send)?;
+
+    assert_eq!(format!("drained {written}"), read_line(control_rx)?);
+
+    Ok(())
+}
+
+/// Check that host pollables become ready when, and only when, they should, using a fixture which does nothing
+/// except in response to commands we send on a separate control connection.
+pub fn run(network: &Network, address: &str) -> Result<()> {
+    let fixture = resolve(network, address)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?;
+
+    // The fixture pairs each control connection with the next connection it accepts.
+    let (_control, (control_rx, control_tx)) = connect(network, fixture)?;
+    let (data, (data_rx, data_tx)) = connect(network, fixture)?;
+    assert_eq!(to_std(fixture), to_std(data.remote_address()?));
+
+    check_input(&control_tx, &data_rx)?;
+    check_mixed(
+        network,
+        fixture,
+        (&control_rx, &control_tx),
+        (&data_rx, &data_tx),
+    )?;
+    check_output((&control_rx, &control_tx), (&data, &data_tx))?;
+
+    drop((data_rx, data_tx, control_rx, control_tx));
+
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use {
+    crate::{
+        connect, from_std, resolve, to_std, udp_options,
+        wasi::sockets::{
+            network::{ErrorCode, IpAddressFamily, Network},
+            udp_create_socket,
+        },
+        with_port,
+    },
+    anyhow::{anyhow, Result},
+    std::net::{Ipv6Addr, SocketAddr, SocketAddrV6},
+};
+
+/// Connect to a link-local address given as `[<address>%<scope ID>]:<port>` and check that the scope ID survives
+/// the trip through the host in both directions, and that a nonzero flow label survives the trip into it.
+pub fn run(network: &Network, address: &str) -> Result<()> {
+    // Nonzero values must survive conversion to and from the WIT representation unchanged.
+    let sample = SocketAddr::V6(SocketAddrV6::new(
+        Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1),
+        4242,
+        0xbeef,
+        7,
+    ));
+    assert_eq!(sample, to_std(from_std(sample)));
+
+    let fixture = resolve(network, address)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?;
+
+    let SocketAddr::V6(expected) = to_std(fixture) else {
+        return Err(anyhow!("expected an IPv6 address; got {address:?}"));
+    };
+    if expected.scope_id() == 0 {
+        return Err(anyhow!("expected a scope ID in {address:?}"));
+    }
+
+    let (client, (rx, tx)) = connect(network, fixture)?;
+
+    // Linux only reports flow labels to sockets which have opted in via `IPV6_FLOWINFO_SEND`, which `wasi-sockets`
+    // doesn't expose, so we expect zero there regardless of what we passed to `connect`.
+    let SocketAddr::V6(remote) = to_std(client.remote_address()?) else {
+        return Err(anyhow!("expected an IPv6 remote address"));
+    };
+    assert_eq!(expected, remote);
+    assert_eq!(0, remote.flowinfo());
+
+    let SocketAddr::V6(local) = to_std(client.local_address()?) else {
+        return Err(anyhow!("expected an IPv6 local address"));
+    };
+    assert_eq!(expected.scope_id(), local.scope_id());
+    assert_eq!(0, local.flowinfo());
+
+    let message = b"So rested he by the Tumtum tree";
+    tx.blocking_write_and_flush(message)?;
+
+    let mut buffer = Vec::with_capacity(message.len());
+    while buffer.len() < message.len() {
+        buffer.extend(rx.blocking_read((message.len() - buffer.len()).try_into()?)?);
+    }
+    assert_eq!(message.as_slice(), &buffer);
+
+    drop((rx, tx));
+
+    // The kernel won't report a nonzero flow label back to us (see above), but the host remembers the exact address
+    // a UDP stream was connected to and refuses datagrams addressed anywhere else, so this checks that the flow label
+    // we pass in reaches the host intact.
+    let labelled = from_std(SocketAddr::V6(SocketAddrV6::new(
+        *expected.ip(),
+        expected.port(),
+        0xbeef,
+        expected.scope_id(),
+    )));
+
+    let socket = udp_create_socket::create_udp_socket(IpAddressFamily::Ipv6)?;
+    udp_options::bind(network, &socket, with_port(fixture, 0))?;
+    let (rx, tx) = socket.stream(Some(labelled))?;
+
+    udp_options::send(&tx, b"And stood awhile in thought".to_vec(), Some(labelled))?;
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        udp_options::send(&tx, b"And stood awhile in thought".to_vec(), Some(fixture))
+    );
+
+    drop((rx, tx));
+
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use {
+    crate::{
+        bind, connect, family_of, listen, read_line, resolve, unspecified,
+        wasi::{
+            clocks::monotonic_clock::Duration,
+            sockets::{
+                network::{ErrorCode, IpAddressFamily, Network},
+                tcp::TcpSocket,
+                tcp_create_socket,
+            },
+        },
+    },
+    anyhow::{anyhow, Result},
+};
+
+const SECOND: Duration = 1_000_000_000;
+
+/// Set each option to a valid value and read it back, then check that zero is rejected where documented.
+fn round_trip(socket: &TcpSocket) -> Result<()> {
+    socket.set_keep_alive_enabled(true)?;
+    assert!(socket.keep_alive_enabled()?);
+    socket.set_keep_alive_enabled(false)?;
+    assert!(!socket.keep_alive_enabled()?);
+
+    socket.set_keep_alive_idle_time(42 * SECOND)?;
+    assert_eq!(42 * SECOND, socket.keep_alive_idle_time()?);
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        socket.set_keep_alive_idle_time(0)
+    );
+
+    socket.set_keep_alive_interval(7 * SECOND)?;
+    assert_eq!(7 * SECOND, socket.keep_alive_interval()?);
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        socket.set_keep_alive_interval(0)
+    );
+
+    socket.set_keep_alive_count(5)?;
+    assert_eq!(5, socket.keep_alive_count()?);
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        socket.set_keep_alive_count(0)
+    );
+
+    socket.set_hop_limit(33)?;
+    assert_eq!(33, socket.hop_limit()?);
+    assert_eq!(Err(ErrorCode::InvalidArgument), socket.set_hop_limit(0));
+
+    // Buffer sizes may be clamped or rounded by the OS, so we only check that something sensible comes back.
+    socket.set_receive_buffer_size(64 * 1024)?;
+    assert!(socket.receive_buffer_size()? > 0);
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        socket.set_receive_buffer_size(0)
+    );
+
+    socket.set_send_buffer_size(64 * 1024)?;
+    assert!(socket.send_buffer_size()? > 0);
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        socket.set_send_buffer_size(0)
+    );
+
+    Ok(())
+}
+
+fn check_unbound(family: IpAddressFamily) -> Result<()> {
+    let socket = tcp_create_socket::create_tcp_socket(family)?;
+    assert_eq!(family, socket.address_family());
+    assert!(matches!(
+        socket.local_address(),
+        Err(ErrorCode::InvalidState)
+    ));
+
+    round_trip(&socket)?;
+
+    socket.set_listen_backlog_size(16)?;
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        socket.set_listen_backlog_size(0)
+    );
+
+    Ok(())
+}
+
+fn check_bound(network: &Network, family: IpAddressFamily) -> Result<()> {
+    let socket = tcp_create_socket::create_tcp_socket(family)?;
+    bind(network, &socket, unspecified(family, 0))?;
+
+    round_trip(&socket)?;
+
+    socket.set_listen_backlog_size(16)?;
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        socket.set_listen_backlog_size(0)
+    );
+
+    Ok(())
+}
+
+fn check_listening(network: &Network, family: IpAddressFamily) -> Result<()> {
+    let socket = tcp_create_socket::create_tcp_socket(family)?;
+    bind(network, &socket, unspecified(family, 0))?;
+    listen(&socket)?;
+    assert!(socket.is_listening());
+
+    round_trip(&socket)?;
+
+    // Changing the backlog after `listen` is allowed to be unsupported.
+    match socket.set_listen_backlog_size(32) {
+        Ok(()) | Err(ErrorCode::NotSupported) => {}
+        Err(error) => return Err(anyhow!(error)),
+    }
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        socket.set_listen_backlog_size(0)
+    );
+
+    Ok(())
+}
+
+fn check_connected(network: &Network, address: &str) -> Result<()> {
+    for address in resolve(network, address)? {
+        if let Ok((socket, (rx, tx))) = connect(network, address) {
+            round_trip(&socket)?;
+
+            assert_eq!(
+                Err(ErrorCode::InvalidState),
+                socket.set_listen_backlog_size(16)
+            );
+
+            // Leave the socket in a known configuration and ask the fixture to compare what we read back with
+            // what the host OS reports for the same socket.
+            socket.set_keep_alive_enabled(true)?;
+            socket.set_keep_alive_idle_time(42 * SECOND)?;
+            socket.set_keep_alive_interval(7 * SECOND)?;
+            socket.set_keep_alive_count(5)?;
+            socket.set_hop_limit(33)?;
+            socket.set_receive_buffer_size(64 * 1024)?;
+            socket.set_send_buffer_size(64 * 1024)?;
+
+            let report = format!(
+                "keep-alive-enabled {}\n\
+                 keep-alive-idle-time {}\n\
+                 keep-alive-interval {}\n\
+                 keep-alive-count {}\n\
+                 hop-limit {}\n\
+                 receive-buffer-size {}\n\
+                 send-buffer-size {}\n\
+                 \n",
+                u8::from(socket.keep_alive_enabled()?),
+                socket.keep_alive_idle_time()?,
+                socket.keep_alive_interval()?,
+                socket.keep_alive_count()?,
+                socket.hop_limit()?,
+                socket.receive_buffer_size()?,
+                socket.send_buffer_size()?,
+            );
+            tx.blocking_write_and_flush(report.as_bytes())?;
+
+            assert_eq!("ok", read_line(&rx)?);
+
+            drop((rx, tx));
+
+            return Ok(());
+        }
+    }
+
+    Err(anyhow!("unable to connect to {address:?}"))
+}
+
+pub fn run(network: &Network, address: &str) -> Result<()> {
+    let family = resolve(network, address)?
+        .first()
+        .map(family_of)
+        .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?;
+
+    check_unbound(family)?;
+    check_bound(network, family)?;
+    check_listening(network, family)?;
+    check_connected(network, address)
+}
@@ -0,0 +1,208 @@
+use {
+    crate::{
+        family_of, port, resolve, to_std, unspecified,
+        wasi::sockets::{
+            network::{ErrorCode, IpAddressFamily, IpSocketAddress, Network},
+            udp::{
+                IncomingDatagram, IncomingDatagramStream, OutgoingDatagram, OutgoingDatagramStream,
+                UdpSocket,
+            },
+            udp_create_socket,
+        },
+        with_port,
+    },
+    anyhow::{anyhow, Result},
+};
+
+/// Largest payload which fits in a single datagram without fragmentation headers: 65535 minus the IPv4 header (20
+/// bytes) and UDP header (8 bytes).  IPv6 headers don't count against the payload length, so the limit there is
+/// 65535 minus the UDP header alone.
+fn max_payload(family: IpAddressFamily) -> usize {
+    match family {
+        IpAddressFamily::Ipv4 => 65507,
+        IpAddressFamily::Ipv6 => 65527,
+    }
+}
+
+pub fn bind(
+    network: &Network,
+    socket: &UdpSocket,
+    address: IpSocketAddress,
+) -> Result<(), ErrorCode> {
+    socket.start_bind(network, address)?;
+    loop {
+        match socket.finish_bind() {
+            Err(ErrorCode::WouldBlock) => socket.subscribe().block(),
+            result => break result,
+        }
+    }
+}
+
+pub fn send(
+    tx: &OutgoingDatagramStream,
+    data: Vec<u8>,
+    remote_address: Option<IpSocketAddress>,
+) -> Result<(), ErrorCode> {
+    while tx.check_send()? == 0 {
+        tx.subscribe().block();
+    }
+
+    let count = tx.send(&[OutgoingDatagram {
+        data,
+        remote_address,
+    }])?;
+    assert_eq!(1, count);
+
+    Ok(())
+}
+
+pub fn receive(rx: &IncomingDatagramStream) -> Result<IncomingDatagram> {
+    loop {
+        if let Some(datagram) = rx.receive(1)?.into_iter().next() {
+            break Ok(datagram);
+        }
+        rx.subscribe().block();
+    }
+}
+
+/// Send `size` bytes and expect the fixture to echo them back unchanged.
+fn echo(
+    rx: &IncomingDatagramStream,
+    tx: &OutgoingDatagramStream,
+    size: usize,
+    remote_address: Option<IpSocketAddress>,
+    fixture: IpSocketAddress,
+) -> Result<()> {
+    let data = (0..size).map(|i| i as u8).collect::<Vec<_>>();
+    send(tx, data.clone(), remote_address)?;
+
+    let datagram = receive(rx)?;
+    assert_eq!(to_std(fixture), to_std(datagram.remote_address));
+    assert_eq!(data, datagram.data);
+
+    Ok(())
+}
+
+fn round_trip(socket: &UdpSocket) -> Result<()> {
+    socket.set_unicast_hop_limit(33)?;
+    assert_eq!(33, socket.unicast_hop_limit()?);
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        socket.set_unicast_hop_limit(0)
+    );
+
+    // Buffer sizes may be clamped or rounded by the OS, so we only check that something sensible comes back.
+    socket.set_receive_buffer_size(64 * 1024)?;
+    assert!(socket.receive_buffer_size()? > 0);
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        socket.set_receive_buffer_size(0)
+    );
+
+    socket.set_send_buffer_size(64 * 1024)?;
+    assert!(socket.send_buffer_size()? > 0);
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        socket.set_send_buffer_size(0)
+    );
+
+    Ok(())
+}
+
+fn check_unconnected(network: &Network, fixture: IpSocketAddress) -> Result<()> {
+    let family = family_of(&fixture);
+    let socket = udp_create_socket::create_udp_socket(family)?;
+    assert_eq!(family, socket.address_family());
+
+    round_trip(&socket)?;
+
+    assert!(matches!(socket.stream(None), Err(ErrorCode::InvalidState)));
+
+    bind(network, &socket, unspecified(family, 0))?;
+    round_trip(&socket)?;
+
+    let (rx, tx) = socket.stream(None)?;
+    assert!(matches!(
+        socket.remote_address(),
+        Err(ErrorCode::InvalidState)
+    ));
+
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        send(&tx, b"no destination".to_vec(), None)
+    );
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        send(&tx, b"port zero".to_vec(), Some(with_port(fixture, 0)))
+    );
+
+    echo(&rx, &tx, 0, Some(fixture), fixture)?;
+    echo(&rx, &tx, 1472, Some(fixture), fixture)?;
+    echo(&rx, &tx, max_payload(family), Some(fixture), fixture)?;
+
+    assert_eq!(
+        Err(ErrorCode::DatagramTooLarge),
+        send(&tx, vec![0; max_payload(family) + 1], Some(fixture))
+    );
+
+    Ok(())
+}
+
+fn check_connected(network: &Network, fixture: IpSocketAddress) -> Result<()> {
+    let family = family_of(&fixture);
+    let socket = udp_create_socket::create_udp_socket(family)?;
+    bind(network, &socket, unspecified(family, 0))?;
+
+    assert!(matches!(
+        socket.stream(Some(with_port(fixture, 0))),
+        Err(ErrorCode::InvalidArgument)
+    ));
+
+    let (rx, tx) = socket.stream(Some(fixture))?;
+    assert_eq!(to_std(fixture), to_std(socket.remote_address()?));
+
+    echo(&rx, &tx, 0, None, fixture)?;
+    echo(&rx, &tx, 1472, Some(fixture), fixture)?;
+
+    // Any port other than the fixture's will do here.
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        send(
+            &tx,
+            b"wrong destination".to_vec(),
+            Some(with_port(fixture, port(fixture) ^ 1))
+        )
+    );
+    assert_eq!(
+        Err(ErrorCode::DatagramTooLarge),
+        send(&tx, vec![0; max_payload(family) + 1], None)
+    );
+
+    // Dissociate from the fixture and switch to per-datagram addressing on the same socket.
+    drop((rx, tx));
+    let (rx, tx) = socket.stream(None)?;
+    assert!(matches!(
+        socket.remote_address(),
+        Err(ErrorCode::InvalidState)
+    ));
+
+    assert_eq!(
+        Err(ErrorCode::InvalidArgument),
+        send(&tx, b"no destination".to_vec(), None)
+    );
+    echo(&rx, &tx, 1472, Some(fixture), fixture)?;
+
+    drop((rx, tx));
+
+    Ok(())
+}
+
+pub fn run(network: &Network, address: &str) -> Result<()> {
+    let fixture = resolve(network, address)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("no addresses found for {address:?}"))?;
+
+    check_unconnected(network, fixture)?;
+    check_connected(network, fixture)
+}
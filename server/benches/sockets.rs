@@ -0,0 +1,208 @@
+//! Connect latency, round trip latency, and echo throughput for each guest, compared with a native `tokio`
+//! baseline.
+//!
+//! Run with `cargo bench`.  Results are written to stdout as a JSON array with one object per client, e.g.:
+//!
+//! ```json
+//! [{"client":"direct","connect_ns":52113,"round_trip_ns":30871,"bulk_bytes":16777216,"bulk_ns":912345678,
+//!   "throughput_bytes_per_sec":18389217.5}]
+//! ```
+//!
+//! `SOCKETS_BENCH_ITERATIONS` and `SOCKETS_BENCH_BULK_BYTES` override the number of connects/round trips and the
+//! size of the bulk transfer, respectively.
+
+#![deny(warnings)]
+
+#[path = "../src/harness.rs"]
+mod harness;
+
+use {
+    anyhow::{Result, anyhow},
+    harness::{
+        Limits, build_component_in, build_python_component, run, shared, spawn_server, store, wasi,
+    },
+    serde::{Deserialize, Serialize},
+    sockets_server::serve_echo,
+    std::{
+        env,
+        net::{Ipv4Addr, SocketAddr},
+        time::{Duration, Instant},
+    },
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    },
+    wasmtime_wasi::pipe::MemoryOutputPipe,
+};
+
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// What each guest prints, in nanoseconds (medians in the case of latencies).
+#[derive(Deserialize)]
+struct Measurement {
+    connect_ns: u64,
+    round_trip_ns: u64,
+    bulk_bytes: u64,
+    bulk_ns: u64,
+}
+
+#[derive(Serialize)]
+struct Report {
+    client: &'static str,
+    connect_ns: u64,
+    round_trip_ns: u64,
+    bulk_bytes: u64,
+    bulk_ns: u64,
+    throughput_bytes_per_sec: f64,
+}
+
+impl Report {
+    fn new(client: &'static str, measurement: Measurement) -> Self {
+        Self {
+            client,
+            connect_ns: measurement.connect_ns,
+            round_trip_ns: measurement.round_trip_ns,
+            bulk_bytes: measurement.bulk_bytes,
+            bulk_ns: measurement.bulk_ns,
+            throughput_bytes_per_sec: measurement.bulk_bytes as f64
+                / Duration::from_nanos(measurement.bulk_ns).as_secs_f64(),
+        }
+    }
+}
+
+fn env_or(name: &str, default: usize) -> Result<usize> {
+    env::var(name).map_or(Ok(default), |value| Ok(value.parse()?))
+}
+
+fn median(mut samples: Vec<Duration>) -> Duration {
+    samples.sort();
+    samples[samples.len() / 2]
+}
+
+/// Same measurements as the guests take, but using `tokio` natively.
+async fn bench_native(
+    address: SocketAddr,
+    iterations: usize,
+    bulk_bytes: usize,
+) -> Result<Measurement> {
+    let mut connects = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let stream = TcpStream::connect(address).await?;
+        connects.push(start.elapsed());
+        drop(stream);
+    }
+
+    let mut stream = TcpStream::connect(address).await?;
+
+    let message = [42_u8; 64];
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut round_trips = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        stream.write_all(&message).await?;
+        stream.read_exact(&mut buffer[..message.len()]).await?;
+        round_trips.push(start.elapsed());
+    }
+
+    let chunk = vec![42_u8; CHUNK_SIZE];
+    let start = Instant::now();
+    let mut remaining = bulk_bytes;
+    while remaining > 0 {
+        let length = remaining.min(CHUNK_SIZE);
+        stream.write_all(&chunk[..length]).await?;
+        stream.read_exact(&mut buffer[..length]).await?;
+        remaining -= length;
+    }
+    let bulk = start.elapsed();
+
+    Ok(Measurement {
+        connect_ns: median(connects).as_nanos().try_into()?,
+        round_trip_ns: median(round_trips).as_nanos().try_into()?,
+        bulk_bytes: bulk_bytes.try_into()?,
+        bulk_ns: bulk.as_nanos().try_into()?,
+    })
+}
+
+async fn bench_guest(
+    component: &[u8],
+    address: SocketAddr,
+    iterations: usize,
+    bulk_bytes: usize,
+) -> Result<Measurement> {
+    let component = shared().component(component)?;
+
+    let stdout = MemoryOutputPipe::new(64 * 1024);
+    let iterations = iterations.to_string();
+    let bulk_bytes = bulk_bytes.to_string();
+    let limits = Limits {
+        budget: Duration::from_secs(600),
+        ..Limits::default()
+    };
+    let mut store = store(
+        wasi(
+            None,
+            address,
+            &["bench", iterations.as_str(), bulk_bytes.as_str()],
+        )
+        .stdout(stdout.clone())
+        .build(),
+        &limits,
+    )?;
+
+    run(&mut store, &component).await?;
+
+    let output = String::from_utf8(stdout.contents().to_vec())?;
+    let line = output
+        .lines()
+        .last()
+        .ok_or_else(|| anyhow!("guest produced no output"))?;
+
+    Ok(serde_json::from_str(line)?)
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() -> Result<()> {
+    let iterations = env_or("SOCKETS_BENCH_ITERATIONS", 100)?;
+    if iterations == 0 {
+        return Err(anyhow!("SOCKETS_BENCH_ITERATIONS must be at least 1"));
+    }
+    let bulk_bytes = env_or("SOCKETS_BENCH_BULK_BYTES", 16 * 1024 * 1024)?;
+
+    let (_tx, address) = spawn_server(serve_echo((Ipv4Addr::LOCALHOST, 0).into())).await?;
+
+    let guests = [
+        (
+            "direct",
+            build_component_in("../client", "sockets-client", true).await?,
+        ),
+        (
+            "std",
+            build_component_in("../client-std", "sockets-client-std", true).await?,
+        ),
+        (
+            "tokio",
+            build_component_in("../client-tokio", "sockets-client-tokio", true).await?,
+        ),
+        (
+            "python",
+            build_python_component(&["../client-python"], "test:test/command").await?,
+        ),
+    ];
+
+    let mut reports = vec![Report::new(
+        "native-tokio",
+        bench_native(address, iterations, bulk_bytes).await?,
+    )];
+
+    for (client, component) in guests {
+        reports.push(Report::new(
+            client,
+            bench_guest(&component, address, iterations, bulk_bytes).await?,
+        ));
+    }
+
+    println!("{}", serde_json::to_string(&reports)?);
+
+    Ok(())
+}
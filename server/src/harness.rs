@@ -0,0 +1,524 @@
+//! Test host machinery shared by the unit tests in `lib.rs` and the benchmarks in `benches`.
+//!
+//! This module is compiled separately into each of those targets (via `#[path]` in the latter case), and each
+//! uses a different subset of it.
+
+use {
+    anyhow::{Result, anyhow},
+    futures::{channel::oneshot, future},
+    hickory_proto::{
+        op::{Message, MessageType, OpCode, Query, ResponseCode},
+        rr::{Name, RData, RecordType},
+    },
+    std::{
+        collections::HashMap,
+        env,
+        future::Future,
+        hash::{DefaultHasher, Hash, Hasher},
+        net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+        path::Path,
+        sync::{
+            Mutex, Once, OnceLock,
+            atomic::{AtomicU16, Ordering},
+        },
+        thread,
+        time::Duration,
+    },
+    tempfile::NamedTempFile,
+    tokio::{fs, net::UdpSocket, process::Command, task, time},
+    wasmtime::{
+        Config, Engine, Store, StoreContextMut, StoreLimits, StoreLimitsBuilder,
+        component::{Component, Linker, Resource, ResourceTable},
+    },
+    wasmtime_wasi::{
+        SocketError, SocketResult, WasiCtx, WasiCtxBuilder, WasiImpl, WasiView,
+        bindings::{
+            self,
+            sockets::{
+                ip_name_lookup::{self, ResolveAddressStream},
+                network::{ErrorCode, IpAddress, IpAddressFamily, Network},
+                tcp_create_socket, udp_create_socket,
+            },
+        },
+        runtime,
+    },
+};
+
+pub mod scenarios {
+    wasmtime::component::bindgen!({
+        path: "../client/wit",
+        world: "scenario-runner",
+        async: true,
+    });
+}
+
+pub use scenarios::ScenarioRunner;
+
+/// Bounds on what a guest may consume over the course of a single test.
+pub struct Limits {
+    /// Wall-clock time after which the guest is interrupted at its next epoch check.
+    pub budget: Duration,
+    /// Maximum size of any linear memory, in bytes.
+    pub memory_size: usize,
+    /// Maximum number of elements in any table.
+    pub table_elements: usize,
+    /// Maximum number of TCP and UDP sockets the guest may create.
+    pub max_sockets: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            budget: Duration::from_secs(60),
+            memory_size: 256 * 1024 * 1024,
+            table_elements: 100_000,
+            max_sockets: 64,
+        }
+    }
+}
+
+pub struct SocketsCtx {
+    pub table: ResourceTable,
+    wasi: WasiCtx,
+    limits: StoreLimits,
+    max_sockets: usize,
+    sockets_created: usize,
+    /// DNS server to send `ip-name-lookup` queries to instead of the system resolver, if any.
+    pub resolver: Option<SocketAddr>,
+}
+
+impl SocketsCtx {
+    /// Return how many sockets (TCP or UDP) the guest has created so far.
+    #[allow(dead_code)]
+    pub fn sockets_created(&self) -> usize {
+        self.sockets_created
+    }
+
+    /// Create a socket using `create`, unless the guest has already used up its socket allowance.
+    fn new_socket<R>(
+        &mut self,
+        create: impl FnOnce(&mut WasiImpl<&mut Self>) -> SocketResult<R>,
+    ) -> wasmtime::Result<(Result<R, ErrorCode>,)> {
+        if self.sockets_created >= self.max_sockets {
+            return Ok((Err(ErrorCode::NewSocketLimit),));
+        }
+
+        Ok((match create(&mut WasiImpl(&mut *self)) {
+            Ok(socket) => {
+                self.sockets_created += 1;
+                Ok(socket)
+            }
+            Err(error) => Err(error.downcast()?),
+        },))
+    }
+}
+
+impl WasiView for SocketsCtx {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.wasi
+    }
+}
+
+#[allow(dead_code)]
+pub async fn build_component(src_path: &str, name: &str) -> Result<Vec<u8>> {
+    build_component_in(src_path, name, false).await
+}
+
+/// Build the guest in `src_path`, optionally with optimizations, and return the resulting component.
+pub async fn build_component_in(src_path: &str, name: &str, release: bool) -> Result<Vec<u8>> {
+    let toolchain =
+        env::var("WASI_SOCKETS_TESTS_TOOLCHAIN").unwrap_or_else(|_| "nightly".to_owned());
+
+    let mut args = vec![
+        format!("+{toolchain}"),
+        "build".to_owned(),
+        "--target".to_owned(),
+        "wasm32-wasip2".to_owned(),
+    ];
+    if release {
+        args.push("--release".to_owned());
+    }
+
+    if Command::new("cargo")
+        .current_dir(src_path)
+        .args(&args)
+        .status()
+        .await?
+        .success()
+    {
+        let profile = if release { "release" } else { "debug" };
+        Ok(fs::read(format!("../target/wasm32-wasip2/{profile}/{name}.wasm")).await?)
+    } else {
+        Err(anyhow!("cargo build failed"))
+    }
+}
+
+/// Build a Python component targeting `world` from the modules in `src_paths`, with `app` as the entry point.
+pub async fn build_python_component(src_paths: &[&str], world: &str) -> Result<Vec<u8>> {
+    let tmp = NamedTempFile::new()?;
+    componentize_py::componentize(
+        Some(Path::new("../client/wit")),
+        Some(world),
+        &[],
+        false,
+        Some("command"),
+        &src_paths,
+        &[],
+        "app",
+        tmp.path(),
+        None,
+        false,
+        &HashMap::new(),
+        &HashMap::new(),
+    )
+    .await?;
+    Ok(fs::read(tmp.path()).await?)
+}
+
+/// Replace the `wasi:sockets` socket constructors with versions which enforce `SocketsCtx::max_sockets`.
+pub fn add_socket_limit_to_linker(linker: &mut Linker<SocketsCtx>) -> Result<()> {
+    linker.allow_shadowing(true);
+
+    linker
+        .instance("wasi:sockets/tcp-create-socket@0.2.0")?
+        .func_wrap(
+            "create-tcp-socket",
+            |mut store: StoreContextMut<SocketsCtx>, (family,): (IpAddressFamily,)| {
+                store
+                    .data_mut()
+                    .new_socket(|ctx| tcp_create_socket::Host::create_tcp_socket(ctx, family))
+            },
+        )?;
+
+    linker
+        .instance("wasi:sockets/udp-create-socket@0.2.0")?
+        .func_wrap(
+            "create-udp-socket",
+            |mut store: StoreContextMut<SocketsCtx>, (family,): (IpAddressFamily,)| {
+                store
+                    .data_mut()
+                    .new_socket(|ctx| udp_create_socket::Host::create_udp_socket(ctx, family))
+            },
+        )?;
+
+    linker.allow_shadowing(false);
+
+    Ok(())
+}
+
+/// How long the host waits for `SocketsCtx::resolver` to answer before reporting a temporary failure.
+const RESOLVER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Ask the DNS server at `resolver` for `record_type` records for `name`.
+async fn query(
+    resolver: SocketAddr,
+    name: &Name,
+    record_type: RecordType,
+) -> Result<Result<Vec<IpAddr>, ErrorCode>> {
+    static NEXT_ID: AtomicU16 = AtomicU16::new(0);
+
+    let mut request = Message::new();
+    request
+        .set_id(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true)
+        .add_query(Query::query(name.clone(), record_type));
+
+    let local: SocketAddr = match resolver {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.send_to(&request.to_vec()?, resolver).await?;
+
+    let mut buffer = vec![0; 4096];
+    let Ok(received) = time::timeout(RESOLVER_TIMEOUT, socket.recv(&mut buffer)).await else {
+        return Ok(Err(ErrorCode::TemporaryResolverFailure));
+    };
+    let response = Message::from_vec(&buffer[..received?])?;
+
+    if response.id() != request.id() {
+        return Err(anyhow!("mismatched DNS response id"));
+    }
+
+    Ok(match response.response_code() {
+        ResponseCode::NoError => Ok(response
+            .answers()
+            .iter()
+            .filter_map(|record| match record.data() {
+                Some(RData::A(address)) => Some(IpAddr::V4(address.0)),
+                Some(RData::AAAA(address)) => Some(IpAddr::V6(address.0)),
+                _ => None,
+            })
+            .collect()),
+        ResponseCode::NXDomain => Err(ErrorCode::NameUnresolvable),
+        _ => Err(ErrorCode::PermanentResolverFailure),
+    })
+}
+
+/// Validate `name` and convert it to punycode the same way `wasmtime-wasi` does before handing it to the system
+/// resolver, with the addition that names which can't be encoded as DNS queries (e.g. labels longer than 63 octets)
+/// are rejected here rather than by the resolver.
+fn parse(name: &str) -> Result<url::Host<Name>, ErrorCode> {
+    let host = match url::Host::parse(name) {
+        Ok(host) => host,
+        // `url::Host::parse` doesn't understand bare IPv6 addresses without brackets.
+        Err(_) => match name.parse::<Ipv6Addr>() {
+            Ok(address) => url::Host::Ipv6(address),
+            Err(_) => return Err(ErrorCode::InvalidArgument),
+        },
+    };
+
+    Ok(match host {
+        url::Host::Ipv4(address) => url::Host::Ipv4(address),
+        url::Host::Ipv6(address) => url::Host::Ipv6(address),
+        url::Host::Domain(domain) => {
+            url::Host::Domain(Name::from_ascii(domain).map_err(|_| ErrorCode::InvalidArgument)?)
+        }
+    })
+}
+
+/// Resolve `name` using the DNS server at `resolver`, returning any IPv6 addresses before IPv4 ones.
+async fn lookup(resolver: SocketAddr, name: &Name) -> Result<Result<Vec<IpAddr>, ErrorCode>> {
+    Ok(
+        match future::try_join(
+            query(resolver, name, RecordType::AAAA),
+            query(resolver, name, RecordType::A),
+        )
+        .await?
+        {
+            (Ok(mut addresses), Ok(v4)) => {
+                addresses.extend(v4);
+                Ok(addresses)
+            }
+            (Err(error), _) | (_, Err(error)) => Err(error),
+        },
+    )
+}
+
+/// Replace `wasi:sockets/ip-name-lookup#resolve-addresses` with a version which queries `SocketsCtx::resolver`
+/// when it's set, so tests don't depend on how the machine they run on resolves names.
+///
+/// As with `wasmtime-wasi`'s own implementation, names are validated up front, but the lookup itself runs in the
+/// background, so the guest sees `would-block` from `resolve-next-address` until it completes.
+pub fn add_stub_resolver_to_linker(linker: &mut Linker<SocketsCtx>) -> Result<()> {
+    linker.allow_shadowing(true);
+
+    linker
+        .instance("wasi:sockets/ip-name-lookup@0.2.0")?
+        .func_wrap(
+            "resolve-addresses",
+            |mut store: StoreContextMut<SocketsCtx>,
+             (network, name): (Resource<Network>, String)| {
+                let Some(resolver) = store.data().resolver else {
+                    return Ok((
+                        match ip_name_lookup::Host::resolve_addresses(
+                            &mut WasiImpl(store.data_mut()),
+                            network,
+                            name,
+                        ) {
+                            Ok(stream) => Ok(stream),
+                            Err(error) => Err(error.downcast()?),
+                        },
+                    ));
+                };
+
+                let literal = |address: IpAddr| {
+                    ResolveAddressStream::Done(Ok(vec![IpAddress::from(address)].into_iter()))
+                };
+
+                let stream = match parse(&name) {
+                    Ok(url::Host::Ipv4(address)) => literal(address.into()),
+                    Ok(url::Host::Ipv6(address)) => literal(address.into()),
+                    Ok(url::Host::Domain(name)) => {
+                        ResolveAddressStream::Waiting(runtime::spawn(async move {
+                            match lookup(resolver, &name).await {
+                                Ok(Ok(addresses)) => {
+                                    Ok(addresses.into_iter().map(IpAddress::from).collect())
+                                }
+                                Ok(Err(code)) => Err(code.into()),
+                                Err(error) => Err(SocketError::trap(error)),
+                            }
+                        }))
+                    }
+                    Err(code) => return Ok((Err(code),)),
+                };
+
+                Ok((Ok(store.data_mut().table.push(stream)?),))
+            },
+        )?;
+
+    linker.allow_shadowing(false);
+
+    Ok(())
+}
+
+/// Start the fixture returned by `serve`, which will run until the returned sender is dropped.
+pub async fn spawn_server(
+    serve: impl Future<
+        Output = Result<(
+            impl Future<Output = Result<()>> + Unpin + Send + 'static,
+            SocketAddr,
+        )>,
+    >,
+) -> Result<(oneshot::Sender<()>, SocketAddr)> {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(pretty_env_logger::init);
+
+    let (server, address) = serve.await?;
+
+    let (tx, rx) = oneshot::channel::<()>();
+
+    task::spawn(async move {
+        drop(future::select(server, rx).await);
+    });
+
+    Ok((tx, address))
+}
+
+/// How often the shared engine's epoch advances.  Guest budgets are measured in multiples of this.
+const EPOCH_TICK: Duration = Duration::from_millis(100);
+
+/// An `Engine` and `Linker` shared by every test in the suite, plus a cache of components compiled with that
+/// engine.
+pub struct Shared {
+    pub engine: Engine,
+    pub linker: Linker<SocketsCtx>,
+    components: Mutex<HashMap<u64, Component>>,
+}
+
+impl Shared {
+    /// Compile `bytes`, or return the previously compiled component if we've seen them before.
+    pub fn component(&self, bytes: &[u8]) -> Result<Component> {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        let key = hasher.finish();
+
+        if let Some(component) = self.components.lock().unwrap().get(&key) {
+            return Ok(component.clone());
+        }
+
+        let component = Component::new(&self.engine, bytes)?;
+        self.components
+            .lock()
+            .unwrap()
+            .insert(key, component.clone());
+        Ok(component)
+    }
+}
+
+/// Return the suite-wide `Shared` instance, creating it on first use.
+///
+/// Since the engine is shared, we can't interrupt one guest by bumping the epoch without interrupting all of them.
+/// Instead, a background thread advances the epoch every `EPOCH_TICK`, and each store sets its deadline relative to
+/// that.
+pub fn shared() -> &'static Shared {
+    static SHARED: OnceLock<Shared> = OnceLock::new();
+
+    SHARED.get_or_init(|| {
+        let engine = engine().expect("failed to create engine");
+        let linker = linker(&engine).expect("failed to create linker");
+
+        thread::spawn({
+            let engine = engine.clone();
+            move || {
+                loop {
+                    thread::sleep(EPOCH_TICK);
+                    engine.increment_epoch();
+                }
+            }
+        });
+
+        Shared {
+            engine,
+            linker,
+            components: Mutex::new(HashMap::new()),
+        }
+    })
+}
+
+fn engine() -> Result<Engine> {
+    let mut config = Config::new();
+    config.wasm_component_model(true);
+    config.async_support(true);
+    config.epoch_interruption(true);
+
+    Engine::new(&config)
+}
+
+fn linker(engine: &Engine) -> Result<Linker<SocketsCtx>> {
+    let mut linker = Linker::new(engine);
+
+    wasmtime_wasi::add_to_linker_async(&mut linker)?;
+    add_socket_limit_to_linker(&mut linker)?;
+    add_stub_resolver_to_linker(&mut linker)?;
+
+    Ok(linker)
+}
+
+/// Create a `WasiCtxBuilder` which passes the fixture's address, followed by `args`, to the guest.
+pub fn wasi(hostname: Option<&str>, address: SocketAddr, args: &[&str]) -> WasiCtxBuilder {
+    let mut builder = WasiCtxBuilder::new();
+    builder
+        .inherit_stdio()
+        .inherit_network()
+        .allow_ip_name_lookup(true)
+        .arg("sockets-client")
+        .arg(
+            hostname
+                .map(|h| format!("{h}:{}", address.port()))
+                .unwrap_or_else(|| format!("{address}")),
+        )
+        .args(args);
+    builder
+}
+
+pub fn store(wasi: WasiCtx, limits: &Limits) -> Result<Store<SocketsCtx>> {
+    let mut store = Store::new(
+        &shared().engine,
+        SocketsCtx {
+            table: ResourceTable::new(),
+            wasi,
+            limits: StoreLimitsBuilder::new()
+                .memory_size(limits.memory_size)
+                .table_elements(limits.table_elements.try_into()?)
+                .build(),
+            max_sockets: limits.max_sockets,
+            sockets_created: 0,
+            resolver: None,
+        },
+    );
+    store.limiter(|ctx| &mut ctx.limits);
+    store.set_epoch_deadline(
+        (limits.budget.as_millis() / EPOCH_TICK.as_millis())
+            .max(1)
+            .try_into()?,
+    );
+    store.epoch_deadline_trap();
+
+    Ok(store)
+}
+
+pub async fn run(store: &mut Store<SocketsCtx>, component: &Component) -> Result<()> {
+    let command =
+        bindings::Command::instantiate_async(&mut *store, component, &shared().linker).await?;
+
+    command
+        .wasi_cli_run()
+        .call_run(&mut *store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
+}
+
+/// Instantiate `component` without running it, returning a handle to its `test:test/scenarios` export.
+#[allow(dead_code)]
+pub async fn instantiate_scenarios(
+    store: &mut Store<SocketsCtx>,
+    component: &Component,
+) -> Result<ScenarioRunner> {
+    ScenarioRunner::instantiate_async(&mut *store, component, &shared().linker).await
+}
@@ -2,32 +2,59 @@
 
 use {
     anyhow::{Context, Error, Result, anyhow},
-    async_trait::async_trait,
-    bytes::{Buf, Bytes, BytesMut},
+    bytes::{Buf, BufMut, Bytes, BytesMut},
     futures::{
-        FutureExt, SinkExt, StreamExt, TryStreamExt,
-        stream::{self, SplitSink},
+        FutureExt, SinkExt, StreamExt, TryFutureExt, TryStreamExt,
+        channel::mpsc,
+        future::{self, Either},
+        stream,
     },
-    pgwire::{
-        api::{
-            ClientInfo, Type,
-            auth::noop::NoopStartupHandler,
-            portal::{Format, Portal},
-            query::{ExtendedQueryHandler, SimpleQueryHandler, StatementOrPortal},
-            results::{DataRowEncoder, DescribeResponse, FieldInfo, QueryResponse, Response},
-            stmt::NoopQueryParser,
-            store::MemPortalStore,
+    hickory_proto::{
+        op::{Message as DnsMessage, MessageType, ResponseCode},
+        rr::{
+            RData, Record, RecordType,
+            rdata::{A, AAAA},
         },
-        error::PgWireResult,
     },
-    redis_protocol::resp3::{decode, encode, types::Frame},
-    std::{collections::HashMap, future::Future, iter, net::SocketAddr, ops::Deref, sync::Arc},
+    http_body_util::{BodyExt, Full, StreamBody, combinators::BoxBody},
+    hyper::{
+        Method, Request, StatusCode,
+        body::{self, Incoming},
+        server::conn::http1,
+        service::service_fn,
+    },
+    hyper_util::rt::TokioIo,
+    redis_protocol::{
+        resp2::{self, types::Frame as Resp2Frame},
+        resp3::{
+            decode, encode,
+            types::{DecodedFrame, Frame, StreamedFrame},
+        },
+    },
+    socket2::{Domain, Protocol, SockRef, Socket, Type as SocketType},
+    std::{
+        collections::{HashMap, HashSet, hash_map::RandomState},
+        fmt, fs,
+        future::Future,
+        hash::{BuildHasher, Hasher},
+        iter,
+        net::{IpAddr, Ipv6Addr, SocketAddr},
+        ops::Deref,
+        os::fd::{BorrowedFd, RawFd},
+        pin::pin,
+        sync::{
+            Arc, Mutex as StdMutex,
+            atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering},
+        },
+        time::Duration,
+    },
     tokio::{
-        io::{AsyncReadExt, AsyncWriteExt},
-        net::TcpListener,
-        sync::Mutex as AsyncMutex,
-        task,
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream, UdpSocket},
+        sync::{Mutex as AsyncMutex, Notify},
+        task, time,
     },
+    tokio_tungstenite::tungstenite::Message,
     tokio_util::codec::{Decoder, Encoder, Framed},
     tracing::log,
 };
@@ -71,96 +98,341 @@ pub async fn serve_echo(
     ))
 }
 
-#[derive(Default)]
-struct MyQueryHandler {
-    portal_store: Arc<MemPortalStore<String>>,
-    query_parser: Arc<NoopQueryParser>,
+/// Like `serve_echo`, but listening for both IPv4 and IPv6 connections on `[::]` using a single socket with
+/// `IPV6_V6ONLY` disabled.
+///
+/// Each connection starts with a line giving the client's address as seen by the fixture, so IPv4 clients will
+/// appear as IPv4-mapped IPv6 addresses.
+pub async fn serve_dual_stack(port: u16) -> Result<(impl Future<Output = Result<()>>, SocketAddr)> {
+    let address = SocketAddr::from((Ipv6Addr::UNSPECIFIED, port));
+    let socket = Socket::new(Domain::IPV6, SocketType::STREAM, Some(Protocol::TCP))?;
+    socket.set_only_v6(false)?;
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket
+        .bind(&address.into())
+        .with_context(|| format!("Unable to listen on {address}"))?;
+    socket.listen(128)?;
+
+    let listener = TcpListener::from_std(socket.into())?;
+
+    let address = listener.local_addr()?;
+
+    Ok((
+        async move {
+            loop {
+                let (mut stream, peer) = listener.accept().await?;
+
+                task::spawn(
+                    async move {
+                        stream.write_all(format!("{peer}\n").as_bytes()).await?;
+
+                        let mut buffer = vec![0; 1024];
+                        loop {
+                            let count = stream.read(&mut buffer).await?;
+                            if count == 0 {
+                                break Ok::<_, Error>(());
+                            }
+
+                            stream.write_all(&buffer[..count]).await?;
+                        }
+                    }
+                    .map(|result| {
+                        if let Err(e) = result {
+                            log::warn!("error handling connection: {e:?}");
+                        }
+                    }),
+                );
+            }
+        }
+        .boxed(),
+        address,
+    ))
 }
 
-impl MyQueryHandler {
-    fn schema(format: &Format) -> Vec<FieldInfo> {
-        vec![FieldInfo::new(
-            "text".into(),
-            None,
-            None,
-            Type::TEXT,
-            format.format_for(0),
-        )]
+async fn accept_peers(
+    listener: TcpListener,
+    peers: mpsc::UnboundedSender<(&'static str, SocketAddr)>,
+) -> Result<()> {
+    loop {
+        let (mut stream, peer) = listener.accept().await?;
+        peers.unbounded_send(("tcp", peer))?;
+
+        task::spawn(
+            async move {
+                stream.write_all(format!("{peer}\n").as_bytes()).await?;
+
+                let mut buffer = vec![0; 1024];
+                loop {
+                    let count = stream.read(&mut buffer).await?;
+                    if count == 0 {
+                        break Ok::<_, Error>(());
+                    }
+
+                    stream.write_all(&buffer[..count]).await?;
+                }
+            }
+            .map(|result| {
+                if let Err(e) = result {
+                    log::warn!("error handling connection: {e:?}");
+                }
+            }),
+        );
     }
 }
 
-#[async_trait]
-impl SimpleQueryHandler for MyQueryHandler {
-    async fn do_query<'a, C>(&self, _client: &C, _query: &'a str) -> PgWireResult<Vec<Response<'a>>>
-    where
-        C: ClientInfo + Unpin + Send + Sync,
-    {
-        todo!()
+async fn echo_datagrams(
+    socket: UdpSocket,
+    peers: mpsc::UnboundedSender<(&'static str, SocketAddr)>,
+) -> Result<()> {
+    let mut buffer = vec![0; 65536];
+    loop {
+        let (count, peer) = socket.recv_from(&mut buffer).await?;
+        peers.unbounded_send(("udp", peer))?;
+        socket.send_to(&buffer[..count], peer).await?;
     }
 }
 
-#[async_trait]
-impl ExtendedQueryHandler for MyQueryHandler {
-    type Statement = String;
-    type QueryParser = NoopQueryParser;
-    type PortalStore = MemPortalStore<Self::Statement>;
+/// Echo over both TCP and UDP on the same port, reporting the protocol and address of each TCP client and UDP
+/// sender to `peers`.
+///
+/// As with `serve_dual_stack`, each TCP connection starts with a line giving the client's address as seen by the
+/// fixture.
+pub async fn serve_peers(
+    address: SocketAddr,
+    peers: mpsc::UnboundedSender<(&'static str, SocketAddr)>,
+) -> Result<(impl Future<Output = Result<()>>, SocketAddr)> {
+    let listener = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Unable to listen on {address}"))?;
+
+    let address = listener.local_addr()?;
 
-    fn portal_store(&self) -> Arc<Self::PortalStore> {
-        self.portal_store.clone()
-    }
+    let socket = UdpSocket::bind(address)
+        .await
+        .with_context(|| format!("Unable to bind {address}"))?;
 
-    fn query_parser(&self) -> Arc<Self::QueryParser> {
-        self.query_parser.clone()
-    }
+    Ok((
+        future::try_join(
+            accept_peers(listener, peers.clone()),
+            echo_datagrams(socket, peers),
+        )
+        .map_ok(|_| ())
+        .boxed(),
+        address,
+    ))
+}
+
+/// Pair each control connection with the data connection accepted after it, and then act on line-based commands
+/// received on the control connection:
+///
+/// - `write <n>`: write `n` bytes to the data connection.
+/// - `drain`: read from the data connection until EOF, then reply `drained <n>` on the control connection.
+///
+/// The fixture never reads from or writes to the data connection except as instructed, which lets clients control
+/// exactly when their streams should become ready.  Each command is reported to `commands` once it's been carried
+/// out.
+pub async fn serve_readiness(
+    address: SocketAddr,
+    commands: mpsc::UnboundedSender<String>,
+) -> Result<(impl Future<Output = Result<()>>, SocketAddr)> {
+    let listener = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Unable to listen on {address}"))?;
+
+    let address = listener.local_addr()?;
+
+    Ok((
+        async move {
+            loop {
+                let (control, _) = listener.accept().await?;
+                let (mut data, _) = listener.accept().await?;
+                let commands = commands.clone();
 
-    async fn do_query<'a, C>(
-        &self,
-        _client: &mut C,
-        portal: &'a Portal<Self::Statement>,
-        _max_rows: usize,
-    ) -> PgWireResult<Response<'a>>
-    where
-        C: ClientInfo + Unpin + Send + Sync,
-    {
-        let query = portal.statement().statement();
-        assert_eq!("SELECT $1::TEXT", query.as_str());
+                task::spawn(
+                    async move {
+                        let mut control = BufReader::new(control);
+                        let mut line = String::new();
+                        loop {
+                            line.clear();
+                            if control.read_line(&mut line).await? == 0 {
+                                break Ok::<_, Error>(());
+                            }
+
+                            let command = line.trim_end();
+                            match command.split(' ').collect::<Vec<_>>().as_slice() {
+                                ["write", count] => {
+                                    data.write_all(&vec![42; count.parse()?]).await?
+                                }
+                                ["drain"] => {
+                                    let count =
+                                        tokio::io::copy(&mut data, &mut tokio::io::sink()).await?;
+                                    control
+                                        .get_mut()
+                                        .write_all(format!("drained {count}\n").as_bytes())
+                                        .await?;
+                                }
+                                _ => return Err(anyhow!("unknown command: {command:?}")),
+                            }
 
-        let parameters = portal.parameters();
-        assert_eq!(1, parameters.len());
-        assert_eq!(Some(b"hello world" as &[_]), parameters[0].as_deref());
+                            commands.unbounded_send(command.to_owned())?;
+                        }
+                    }
+                    .map(|result| {
+                        if let Err(e) = result {
+                            log::warn!("error handling connection: {e:?}");
+                        }
+                    }),
+                );
+            }
+        }
+        .boxed(),
+        address,
+    ))
+}
 
-        let schema = Arc::new(Self::schema(&Format::UnifiedText));
-        let mut encoder = DataRowEncoder::new(schema.clone());
-        encoder.encode_field(&"hello world")?;
-        Ok(Response::Query(QueryResponse::new(
-            schema,
-            stream::iter(iter::once(encoder.finish())),
-        )))
+/// Find the socket in this process whose local address is `address`.
+///
+/// Guests run in the same process as the fixtures, so this lets a fixture inspect the host socket backing the
+/// guest's end of a connection.
+fn find_socket(address: SocketAddr) -> Result<Socket> {
+    for entry in fs::read_dir("/proc/self/fd")? {
+        let Ok(fd) = entry?.file_name().to_string_lossy().parse::<RawFd>() else {
+            continue;
+        };
+
+        // SAFETY: The descriptor is only used for the duration of this iteration, and a stale or reused descriptor
+        // will at worst fail `getsockname` or report an unrelated address.
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+
+        if SockRef::from(&fd)
+            .local_addr()
+            .ok()
+            .and_then(|a| a.as_socket())
+            == Some(address)
+        {
+            return Ok(Socket::from(fd.try_clone_to_owned()?));
+        }
     }
 
-    async fn do_describe<C>(
-        &self,
-        _client: &mut C,
-        target: StatementOrPortal<'_, Self::Statement>,
-    ) -> PgWireResult<DescribeResponse>
-    where
-        C: ClientInfo + Unpin + Send + Sync,
-    {
-        match target {
-            StatementOrPortal::Statement(_) => Ok(DescribeResponse::new(
-                Some(vec![Type::TEXT]),
-                Self::schema(&Format::UnifiedText),
-            )),
-            StatementOrPortal::Portal(portal) => Ok(DescribeResponse::new(
-                None,
-                Self::schema(portal.result_column_format()),
-            )),
+    Err(anyhow!("unable to find socket bound to {address}"))
+}
+
+fn check_tcp_options(socket: &Socket, expected: &HashMap<String, u64>) -> Result<()> {
+    let expect = |name: &str| {
+        expected
+            .get(name)
+            .copied()
+            .ok_or_else(|| anyhow!("guest did not report {name}"))
+    };
+
+    let ipv6 = socket.local_addr()?.is_ipv6();
+    let actual = [
+        ("keep-alive-enabled", u64::from(socket.keepalive()?)),
+        (
+            "keep-alive-idle-time",
+            socket.keepalive_time()?.as_nanos().try_into()?,
+        ),
+        (
+            "keep-alive-interval",
+            socket.keepalive_interval()?.as_nanos().try_into()?,
+        ),
+        ("keep-alive-count", socket.keepalive_retries()?.into()),
+        (
+            "hop-limit",
+            if ipv6 {
+                socket.unicast_hops_v6()?
+            } else {
+                socket.ttl()?
+            }
+            .into(),
+        ),
+        (
+            "receive-buffer-size",
+            socket.recv_buffer_size()?.try_into()?,
+        ),
+        ("send-buffer-size", socket.send_buffer_size()?.try_into()?),
+    ];
+
+    for (name, actual) in actual {
+        let expected = expect(name)?;
+        if expected != actual {
+            return Err(anyhow!(
+                "{name}: guest reported {expected} but host reports {actual}"
+            ));
         }
     }
+
+    Ok(())
 }
 
-pub async fn serve_postgres(
+/// Serve connections from guests which report the TCP socket options they've set, one `<name> <value>` pair per
+/// line followed by an empty line.  The fixture compares those values with what the host OS reports for the
+/// guest's socket and replies with either `ok` or a description of the first mismatch.
+pub async fn serve_tcp_options(
+    address: SocketAddr,
+) -> Result<(impl Future<Output = Result<()>>, SocketAddr)> {
+    let listener = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Unable to listen on {address}"))?;
+
+    let address = listener.local_addr()?;
+
+    Ok((
+        async move {
+            loop {
+                let (stream, peer) = listener.accept().await?;
+
+                task::spawn(
+                    async move {
+                        let (rx, mut tx) = stream.into_split();
+                        let mut lines = BufReader::new(rx).lines();
+
+                        let mut expected = HashMap::new();
+                        while let Some(line) = lines.next_line().await? {
+                            if line.is_empty() {
+                                break;
+                            }
+
+                            let (name, value) = line
+                                .split_once(' ')
+                                .ok_or_else(|| anyhow!("malformed option line: {line:?}"))?;
+                            expected.insert(name.to_owned(), value.parse()?);
+                        }
+
+                        let reply = match find_socket(peer)
+                            .and_then(|s| check_tcp_options(&s, &expected))
+                        {
+                            Ok(()) => "ok".to_owned(),
+                            Err(e) => format!("{e}"),
+                        };
+
+                        tx.write_all(format!("{reply}\n").as_bytes()).await?;
+
+                        Ok::<_, Error>(())
+                    }
+                    .map(|result| {
+                        if let Err(e) = result {
+                            log::warn!("error handling connection: {e:?}");
+                        }
+                    }),
+                );
+            }
+        }
+        .boxed(),
+        address,
+    ))
+}
+
+/// Serve connections which exercise TCP half-close.  Each connection starts with a mode line:
+///
+/// - `client-first`: read until the client half-closes, then echo what was read and close.
+/// - `server-first`: write a message and half-close, then read until the client half-closes.
+///
+/// In both cases, whatever the client sent after the mode line is forwarded to `received`.
+pub async fn serve_half_close(
     address: SocketAddr,
+    received: mpsc::UnboundedSender<Vec<u8>>,
 ) -> Result<(impl Future<Output = Result<()>>, SocketAddr)> {
     let listener = TcpListener::bind(address)
         .await
@@ -172,17 +444,33 @@ pub async fn serve_postgres(
         async move {
             loop {
                 let (stream, _) = listener.accept().await?;
+                let received = received.clone();
 
                 task::spawn(
                     async move {
-                        pgwire::tokio::process_socket(
-                            stream,
-                            None,
-                            Arc::new(NoopStartupHandler),
-                            Arc::new(MyQueryHandler::default()),
-                            Arc::new(MyQueryHandler::default()),
-                        )
-                        .await
+                        let (rx, mut tx) = stream.into_split();
+                        let mut rx = BufReader::new(rx);
+
+                        let mut mode = String::new();
+                        rx.read_line(&mut mode).await?;
+
+                        let mut payload = Vec::new();
+                        match mode.trim_end() {
+                            "client-first" => {
+                                rx.read_to_end(&mut payload).await?;
+                                tx.write_all(&payload).await?;
+                            }
+                            "server-first" => {
+                                tx.write_all(b"And stood awhile in thought").await?;
+                                tx.shutdown().await?;
+                                rx.read_to_end(&mut payload).await?;
+                            }
+                            mode => return Err(anyhow!("unknown mode: {mode:?}")),
+                        }
+
+                        received.unbounded_send(payload)?;
+
+                        Ok::<_, Error>(())
                     }
                     .map(|result| {
                         if let Err(e) = result {
@@ -197,40 +485,87 @@ pub async fn serve_postgres(
     ))
 }
 
-pub struct RedisCodec;
+pub async fn serve_udp_echo(
+    address: SocketAddr,
+) -> Result<(impl Future<Output = Result<()>>, SocketAddr)> {
+    let socket = UdpSocket::bind(address)
+        .await
+        .with_context(|| format!("Unable to bind to {address}"))?;
 
-impl Decoder for RedisCodec {
-    type Item = Frame;
-    type Error = anyhow::Error;
+    let address = socket.local_addr()?;
 
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Frame>> {
-        let bytes = Bytes::copy_from_slice(src);
-        Ok(
-            if let Some((frame, length)) =
-                decode::complete::decode(&bytes).map_err(|e| anyhow!("{e}"))?
-            {
-                src.advance(length);
-                Some(frame)
-            } else {
-                None
-            },
-        )
-    }
+    Ok((
+        async move {
+            let mut buffer = vec![0; 65536];
+            loop {
+                let (count, peer) = socket.recv_from(&mut buffer).await?;
+
+                if let Err(e) = socket.send_to(&buffer[..count], peer).await {
+                    log::warn!("error echoing datagram to {peer}: {e:?}");
+                }
+            }
+        }
+        .boxed(),
+        address,
+    ))
 }
 
-impl Encoder<Frame> for RedisCodec {
-    type Error = anyhow::Error;
+/// Return `length` bytes of a pattern which is easy to verify and unlikely to be produced by accident.
+fn pattern(length: usize) -> Vec<u8> {
+    (0..length).map(|i| (i % 251) as u8).collect()
+}
 
-    fn encode(&mut self, frame: Frame, dst: &mut BytesMut) -> Result<()> {
-        let mut buffer = vec![0; 256];
-        let length =
-            encode::complete::encode(&mut buffer, 0, &frame).map_err(|e| anyhow!("{e}"))?;
-        dst.extend_from_slice(&buffer[..length]);
-        Ok(())
-    }
+fn full(bytes: impl Into<Bytes>) -> BoxBody<Bytes, Error> {
+    Full::new(bytes.into())
+        .map_err(|never| match never {})
+        .boxed()
 }
 
-pub async fn serve_redis(
+/// Handle a request to the HTTP fixture.
+///
+/// - `GET /bytes/<length>` returns `length` bytes of `pattern` with a `content-length`.
+/// - `GET /chunked/<count>/<size>` returns `count` chunks of `size` bytes each of `pattern`, using chunked
+///   transfer encoding.
+/// - `POST /echo` returns the request body.
+///
+/// Every response includes an `x-request-count` header giving the number of requests seen so far on the
+/// connection, which lets clients verify that keep-alive is working.
+async fn handle_http(
+    request: Request<Incoming>,
+    count: usize,
+) -> Result<hyper::Response<BoxBody<Bytes, Error>>> {
+    let segments = request
+        .uri()
+        .path()
+        .split('/')
+        .skip(1)
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    let segments = segments.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let body = match (request.method().clone(), segments.as_slice()) {
+        (Method::GET, ["bytes", length]) => full(pattern(length.parse()?)),
+        (Method::GET, ["chunked", chunks, size]) => {
+            let chunk = Bytes::from(pattern(size.parse()?));
+            BodyExt::boxed(StreamBody::new(stream::iter(
+                iter::repeat_n(chunk, chunks.parse()?)
+                    .map(|chunk| Ok::<_, Error>(body::Frame::data(chunk))),
+            )))
+        }
+        (Method::POST, ["echo"]) => full(request.into_body().collect().await?.to_bytes()),
+        _ => {
+            return Ok(hyper::Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(full(Bytes::new()))?);
+        }
+    };
+
+    Ok(hyper::Response::builder()
+        .header("x-request-count", count)
+        .body(body)?)
+}
+
+pub async fn serve_http(
     address: SocketAddr,
 ) -> Result<(impl Future<Output = Result<()>>, SocketAddr)> {
     let listener = TcpListener::bind(address)
@@ -239,87 +574,55 @@ pub async fn serve_redis(
 
     let address = listener.local_addr()?;
 
-    let handle = |tx: Arc<AsyncMutex<SplitSink<_, _>>>, frame: Frame| async move {
-        let unexpected = || Err(anyhow!("don't know how to handle frame: {frame:?}"));
+    Ok((
+        async move {
+            loop {
+                let (stream, _) = listener.accept().await?;
 
-        match &frame {
-            Frame::Array { data, .. } => match data.as_slice() {
-                [Frame::BlobString { data, .. }] => match data.deref() {
-                    b"PING" => {
-                        tx.lock()
-                            .await
-                            .send(Frame::SimpleString {
-                                data: Bytes::copy_from_slice(b"PONG"),
-                                attributes: None,
-                            })
-                            .await?;
-                    }
-                    _ => return unexpected(),
-                },
-                [
-                    Frame::BlobString { data: data1, .. },
-                    Frame::BlobString { data: data2, .. },
-                ] => match (data1.deref(), data2.deref()) {
-                    (b"GET", b"foo") => {
-                        tx.lock()
-                            .await
-                            .send(Frame::BlobString {
-                                data: Bytes::copy_from_slice(b"bar"),
-                                attributes: None,
-                            })
-                            .await?;
-                    }
-                    (b"COMMAND", b"DOCS") => {
-                        tx.lock()
-                            .await
-                            .send(Frame::Map {
-                                data: HashMap::new(),
-                                attributes: None,
-                            })
-                            .await?;
-                    }
-                    _ => return unexpected(),
-                },
-                [
-                    Frame::BlobString { data: data1, .. },
-                    Frame::BlobString { data: data2, .. },
-                    Frame::BlobString { data: data3, .. },
-                ] => match (data1.deref(), data2.deref(), data3.deref()) {
-                    (b"SET", b"foo", b"bar") => {
-                        tx.lock()
-                            .await
-                            .send(Frame::SimpleString {
-                                data: Bytes::copy_from_slice(b"OK"),
-                                attributes: None,
-                            })
-                            .await?;
-                    }
-                    _ => return unexpected(),
-                },
-                [
-                    Frame::BlobString { data: data1, .. },
-                    Frame::BlobString { data: data2, .. },
-                    Frame::BlobString { .. },
-                    Frame::BlobString { .. },
-                ] => match (data1.deref(), data2.deref()) {
-                    (b"CLIENT", b"SETINFO") => {
-                        tx.lock()
-                            .await
-                            .send(Frame::SimpleString {
-                                data: Bytes::copy_from_slice(b"OK"),
-                                attributes: None,
-                            })
+                task::spawn(
+                    async move {
+                        let requests = Arc::new(AtomicUsize::new(0));
+
+                        http1::Builder::new()
+                            .keep_alive(true)
+                            .serve_connection(
+                                TokioIo::new(stream),
+                                service_fn(move |request| {
+                                    handle_http(
+                                        request,
+                                        requests.fetch_add(1, Ordering::Relaxed) + 1,
+                                    )
+                                }),
+                            )
                             .await?;
+
+                        Ok::<_, Error>(())
                     }
-                    _ => return unexpected(),
-                },
-                _ => return unexpected(),
-            },
-            _ => return unexpected(),
+                    .map(|result| {
+                        if let Err(e) = result {
+                            log::warn!("error handling connection: {e:?}");
+                        }
+                    }),
+                );
+            }
         }
+        .boxed(),
+        address,
+    ))
+}
 
-        Ok(())
-    };
+/// Echo text and binary messages over WebSocket.
+///
+/// The fixture pings the client as soon as the connection is upgraded and, once the pong arrives, reports its
+/// payload back as a text message of the form `pong: <payload>`.
+pub async fn serve_websocket(
+    address: SocketAddr,
+) -> Result<(impl Future<Output = Result<()>>, SocketAddr)> {
+    let listener = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Unable to listen on {address}"))?;
+
+    let address = listener.local_addr()?;
 
     Ok((
         async move {
@@ -328,11 +631,26 @@ pub async fn serve_redis(
 
                 task::spawn(
                     async move {
-                        let (tx, mut rx) = Framed::new(stream, RedisCodec).split();
-                        let tx = Arc::new(AsyncMutex::new(tx));
-
-                        while let Some(frame) = rx.try_next().await? {
-                            handle(tx.clone(), frame).await?;
+                        let mut websocket = tokio_tungstenite::accept_async(stream).await?;
+
+                        websocket.send(Message::Ping(b"fixture".to_vec())).await?;
+
+                        // Pings and close frames from the client are answered by `tungstenite` itself.
+                        while let Some(message) = websocket.next().await {
+                            match message? {
+                                message @ (Message::Text(_) | Message::Binary(_)) => {
+                                    websocket.send(message).await?
+                                }
+                                Message::Pong(payload) => {
+                                    websocket
+                                        .send(Message::Text(format!(
+                                            "pong: {}",
+                                            String::from_utf8(payload)?
+                                        )))
+                                        .await?
+                                }
+                                _ => {}
+                            }
                         }
 
                         Ok::<_, Error>(())
@@ -350,193 +668,2584 @@ pub async fn serve_redis(
     ))
 }
 
-#[cfg(test)]
-mod tests {
-    use {
-        super::*,
-        anyhow::anyhow,
-        futures::{channel::oneshot, future},
-        std::{
-            env,
-            net::{Ipv4Addr, Ipv6Addr},
-            path::Path,
-            sync::Once,
-        },
-        tempfile::NamedTempFile,
-        tokio::{fs, process::Command},
-        wasmtime::{
-            Config, Engine, Store,
-            component::{Component, Linker, ResourceTable},
-        },
-        wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiView, bindings},
-    };
-
-    struct SocketsCtx {
-        table: ResourceTable,
-        wasi: WasiCtx,
-    }
+/// Addresses `serve_dns` returns for a name, and how long it waits before doing so.
+#[derive(Clone, Default)]
+pub struct DnsRecord {
+    pub addresses: Vec<IpAddr>,
+    pub delay: Duration,
+}
 
-    impl WasiView for SocketsCtx {
-        fn table(&mut self) -> &mut ResourceTable {
-            &mut self.table
-        }
-        fn ctx(&mut self) -> &mut WasiCtx {
-            &mut self.wasi
+/// Build a response to `request` from `records`, returning it along with how long to wait before sending it.
+fn dns_response(
+    request: &DnsMessage,
+    records: &HashMap<String, DnsRecord>,
+) -> (DnsMessage, Duration) {
+    let mut response = DnsMessage::new();
+    response
+        .set_id(request.id())
+        .set_message_type(MessageType::Response)
+        .set_op_code(request.op_code())
+        .set_authoritative(true)
+        .set_recursion_desired(request.recursion_desired())
+        .set_recursion_available(true)
+        .add_queries(request.queries().iter().cloned());
+
+    let mut delay = Duration::ZERO;
+    for query in request.queries() {
+        let name = query.name().to_utf8().trim_end_matches('.').to_lowercase();
+
+        let Some(record) = records.get(&name) else {
+            response.set_response_code(ResponseCode::NXDomain);
+            continue;
+        };
+
+        delay = delay.max(record.delay);
+
+        for address in &record.addresses {
+            let data = match (address, query.query_type()) {
+                (IpAddr::V4(address), RecordType::A) => RData::A(A(*address)),
+                (IpAddr::V6(address), RecordType::AAAA) => RData::AAAA(AAAA(*address)),
+                _ => continue,
+            };
+
+            response.add_answer(Record::from_rdata(query.name().clone(), 60, data));
         }
     }
 
-    async fn build_component(src_path: &str, name: &str) -> Result<Vec<u8>> {
-        let toolchain =
-            env::var("WASI_SOCKETS_TESTS_TOOLCHAIN").unwrap_or_else(|_| "nightly".to_owned());
+    (response, delay)
+}
 
-        if Command::new("cargo")
-            .current_dir(src_path)
-            .args([
-                format!("+{toolchain}").as_str(),
-                "build",
-                "--target",
-                "wasm32-wasip2",
-            ])
-            .status()
-            .await?
-            .success()
-        {
-            Ok(fs::read(format!("../target/wasm32-wasip2/debug/{name}.wasm")).await?)
-        } else {
-            Err(anyhow!("cargo build failed"))
-        }
-    }
+/// Answer DNS queries for A and AAAA records over UDP using `records`, replying NXDOMAIN for any name not present.
+pub async fn serve_dns(
+    address: SocketAddr,
+    records: HashMap<String, DnsRecord>,
+) -> Result<(impl Future<Output = Result<()>>, SocketAddr)> {
+    let socket = Arc::new(
+        UdpSocket::bind(address)
+            .await
+            .with_context(|| format!("Unable to bind {address}"))?,
+    );
 
-    async fn build_python_component(src_paths: &[&str]) -> Result<Vec<u8>> {
-        let tmp = NamedTempFile::new()?;
-        componentize_py::componentize(
-            Some(Path::new("../client/wit")),
-            Some("wasi:cli/command@0.2.0"),
-            &[],
-            false,
-            Some("command"),
-            &src_paths,
-            &[],
-            "app",
-            tmp.path(),
-            None,
-            false,
-            &HashMap::new(),
-            &HashMap::new(),
-        )
-        .await?;
-        Ok(fs::read(tmp.path()).await?)
-    }
+    let address = socket.local_addr()?;
+
+    Ok((
+        async move {
+            let records = Arc::new(records);
+            let mut buffer = vec![0; 4096];
+            loop {
+                let (count, peer) = socket.recv_from(&mut buffer).await?;
+                let request = DnsMessage::from_vec(&buffer[..count]);
+
+                // Each response is sent from its own task so that slow records don't hold up other queries.
+                task::spawn({
+                    let socket = socket.clone();
+                    let records = records.clone();
+                    async move {
+                        let (response, delay) = dns_response(&request?, &records);
+                        time::sleep(delay).await;
+                        socket.send_to(&response.to_vec()?, peer).await?;
+                        Ok::<_, Error>(())
+                    }
+                    .map(|result| {
+                        if let Err(e) = result {
+                            log::warn!("error handling query: {e:?}");
+                        }
+                    })
+                });
+            }
+        }
+        .boxed(),
+        address,
+    ))
+}
+
+const POSTGRES_PROTOCOL_VERSION: i32 = 196_608;
+const POSTGRES_SSL_REQUEST: i32 = 80_877_103;
+const POSTGRES_CANCEL_REQUEST: i32 = 80_877_102;
+
+/// Maximum size of each `CopyData` message sent for `COPY ... TO STDOUT`.
+const POSTGRES_COPY_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Text format code for parameters and results (as opposed to binary, which is 1).
+const POSTGRES_TEXT: i16 = 0;
+
+/// Messages sent by a Postgres client, per https://www.postgresql.org/docs/current/protocol-message-formats.html.
+#[derive(Debug)]
+enum PostgresRequest {
+    SslRequest,
+    Startup,
+    Cancel {
+        process_id: i32,
+        secret_key: i32,
+    },
+    Query(String),
+    Parse {
+        name: String,
+        query: String,
+    },
+    Bind {
+        portal: String,
+        statement: String,
+        parameter_formats: Vec<i16>,
+        parameters: Vec<Option<Bytes>>,
+        result_formats: Vec<i16>,
+    },
+    Describe {
+        kind: u8,
+        name: String,
+    },
+    Execute {
+        portal: String,
+    },
+    Close {
+        kind: u8,
+        name: String,
+    },
+    Sync,
+    Flush,
+    Terminate,
+    CopyData(Bytes),
+    CopyDone,
+    CopyFail(String),
+}
+
+/// Messages sent by the Postgres fixture.
+#[derive(Debug)]
+enum PostgresReply {
+    /// The single-byte refusal sent in response to an `SSLRequest`.
+    SslRefused,
+    AuthenticationOk,
+    ParameterStatus(&'static str, &'static str),
+    BackendKeyData {
+        process_id: i32,
+        secret_key: i32,
+    },
+    ReadyForQuery(u8),
+    ParseComplete,
+    BindComplete,
+    CloseComplete,
+    ParameterDescription(Vec<PostgresType>),
+    RowDescription(Vec<(&'static str, PostgresType, i16)>),
+    NoData,
+    DataRow(Vec<Option<Bytes>>),
+    CommandComplete(String),
+    EmptyQueryResponse,
+    ErrorResponse(PostgresError),
+    NoticeResponse(PostgresError),
+    CopyInResponse,
+    CopyOutResponse,
+    CopyData(Bytes),
+    CopyDone,
+}
+
+impl PostgresReply {
+    fn tag(&self) -> u8 {
+        match self {
+            Self::SslRefused => b'N',
+            Self::AuthenticationOk => b'R',
+            Self::ParameterStatus(..) => b'S',
+            Self::BackendKeyData { .. } => b'K',
+            Self::ReadyForQuery(_) => b'Z',
+            Self::ParseComplete => b'1',
+            Self::BindComplete => b'2',
+            Self::CloseComplete => b'3',
+            Self::ParameterDescription(_) => b't',
+            Self::RowDescription(_) => b'T',
+            Self::NoData => b'n',
+            Self::DataRow(_) => b'D',
+            Self::CommandComplete(_) => b'C',
+            Self::EmptyQueryResponse => b'I',
+            Self::ErrorResponse(_) => b'E',
+            Self::NoticeResponse(_) => b'N',
+            Self::CopyInResponse => b'G',
+            Self::CopyOutResponse => b'H',
+            Self::CopyData(_) => b'd',
+            Self::CopyDone => b'c',
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum PostgresType {
+    Bool,
+    Int4,
+    Text,
+    Void,
+}
+
+impl PostgresType {
+    fn oid(self) -> u32 {
+        match self {
+            Self::Bool => 16,
+            Self::Int4 => 23,
+            Self::Text => 25,
+            Self::Void => 2278,
+        }
+    }
+
+    fn size(self) -> i16 {
+        match self {
+            Self::Bool => 1,
+            Self::Int4 | Self::Void => 4,
+            Self::Text => -1,
+        }
+    }
+}
+
+/// A value to be sent to the client in a `DataRow`.
+enum PostgresValue {
+    Bool(bool),
+    Int4(i32),
+    Text(String),
+    Void,
+}
+
+impl PostgresValue {
+    fn encode(&self, format: i16) -> Bytes {
+        if format == POSTGRES_TEXT {
+            Bytes::from(match self {
+                Self::Bool(value) => String::from(if *value { "t" } else { "f" }),
+                Self::Int4(value) => value.to_string(),
+                Self::Text(value) => value.clone(),
+                Self::Void => String::new(),
+            })
+        } else {
+            match self {
+                Self::Bool(value) => Bytes::from(vec![u8::from(*value)]),
+                Self::Int4(value) => Bytes::copy_from_slice(&value.to_be_bytes()),
+                Self::Text(value) => Bytes::copy_from_slice(value.as_bytes()),
+                Self::Void => Bytes::new(),
+            }
+        }
+    }
+}
+
+/// An error reported to the client via an `ErrorResponse` (as opposed to one which closes the connection), or a
+/// notice reported via a `NoticeResponse`.
+#[derive(Debug)]
+struct PostgresError {
+    /// `ERROR`, `FATAL` if we're about to close the connection, or `NOTICE`
+    severity: &'static str,
+    /// SQLSTATE code, per https://www.postgresql.org/docs/current/errcodes-appendix.html
+    code: &'static str,
+    message: String,
+}
+
+impl fmt::Display for PostgresError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (SQLSTATE {})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for PostgresError {}
+
+impl PostgresError {
+    fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity: "ERROR",
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Transaction state, as reported to the client in each `ReadyForQuery`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum PostgresTransaction {
+    Idle,
+    Active,
+    /// A statement failed inside a transaction block, so everything but `COMMIT` and `ROLLBACK` will fail until the
+    /// block ends.
+    Failed,
+}
+
+impl PostgresTransaction {
+    fn status(self) -> u8 {
+        match self {
+            Self::Idle => b'I',
+            Self::Active => b'T',
+            Self::Failed => b'E',
+        }
+    }
+}
+
+/// Return the format code which applies to column or parameter `index`, given the format codes sent by the client.
+fn postgres_format(formats: &[i16], index: usize) -> i16 {
+    match formats {
+        [] => POSTGRES_TEXT,
+        [format] => *format,
+        formats => formats.get(index).copied().unwrap_or(POSTGRES_TEXT),
+    }
+}
+
+/// Reads the fields of a Postgres message body, failing rather than panicking on truncated input.
+struct PostgresReader(Bytes);
+
+impl PostgresReader {
+    fn bytes(&mut self, length: usize) -> Result<Bytes> {
+        if self.0.len() < length {
+            return Err(anyhow!("truncated Postgres message"));
+        }
+        Ok(self.0.split_to(length))
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn i16(&mut self) -> Result<i16> {
+        Ok(i16::from_be_bytes(self.bytes(2)?[..].try_into()?))
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_be_bytes(self.bytes(4)?[..].try_into()?))
+    }
+
+    fn count(&mut self) -> Result<usize> {
+        Ok(usize::try_from(self.i16()?)?)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let length = self
+            .0
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| anyhow!("unterminated string in Postgres message"))?;
+        let string = self.bytes(length)?;
+        self.bytes(1)?;
+        Ok(String::from_utf8(string.to_vec())?)
+    }
+}
+
+/// Codec for the Postgres fixture, speaking the frontend/backend protocol (version 3.0).
+#[derive(Default)]
+struct PostgresCodec {
+    /// Whether the startup message has been received, after which every message starts with a type byte.
+    started: bool,
+}
+
+impl Decoder for PostgresCodec {
+    type Item = PostgresRequest;
+    type Error = anyhow::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<PostgresRequest>> {
+        let header = if self.started { 5 } else { 4 };
+        if src.len() < header {
+            return Ok(None);
+        }
+
+        // The length includes itself but not the type byte, if any.
+        let length = usize::try_from(i32::from_be_bytes(src[header - 4..header].try_into()?))?;
+        if length < 4 {
+            return Err(anyhow!("invalid Postgres message length: {length}"));
+        }
+        if src.len() < header - 4 + length {
+            src.reserve(header - 4 + length - src.len());
+            return Ok(None);
+        }
+
+        let tag = if self.started { Some(src[0]) } else { None };
+        let mut message = src.split_to(header - 4 + length).freeze();
+        message.advance(header);
+        let mut body = PostgresReader(message);
+
+        Ok(Some(match tag {
+            None => match body.i32()? {
+                POSTGRES_SSL_REQUEST => PostgresRequest::SslRequest,
+                POSTGRES_CANCEL_REQUEST => PostgresRequest::Cancel {
+                    process_id: body.i32()?,
+                    secret_key: body.i32()?,
+                },
+                // We don't need any of the startup parameters (e.g. `user`), so we ignore them.
+                POSTGRES_PROTOCOL_VERSION => {
+                    self.started = true;
+                    PostgresRequest::Startup
+                }
+                code => return Err(anyhow!("unsupported Postgres startup code: {code}")),
+            },
+            Some(b'Q') => PostgresRequest::Query(body.string()?),
+            // Ignore any parameter types specified by the client; we know what each query expects.
+            Some(b'P') => PostgresRequest::Parse {
+                name: body.string()?,
+                query: body.string()?,
+            },
+            Some(b'B') => {
+                let portal = body.string()?;
+                let statement = body.string()?;
+                let parameter_formats = (0..body.count()?)
+                    .map(|_| body.i16())
+                    .collect::<Result<_>>()?;
+                let parameters = (0..body.count()?)
+                    .map(|_| -> Result<_> {
+                        Ok(match body.i32()? {
+                            -1 => None,
+                            length => Some(body.bytes(usize::try_from(length)?)?),
+                        })
+                    })
+                    .collect::<Result<_>>()?;
+                let result_formats = (0..body.count()?)
+                    .map(|_| body.i16())
+                    .collect::<Result<_>>()?;
+
+                PostgresRequest::Bind {
+                    portal,
+                    statement,
+                    parameter_formats,
+                    parameters,
+                    result_formats,
+                }
+            }
+            Some(b'D') => PostgresRequest::Describe {
+                kind: body.u8()?,
+                name: body.string()?,
+            },
+            Some(b'E') => PostgresRequest::Execute {
+                portal: body.string()?,
+            },
+            Some(b'C') => PostgresRequest::Close {
+                kind: body.u8()?,
+                name: body.string()?,
+            },
+            Some(b'S') => PostgresRequest::Sync,
+            Some(b'H') => PostgresRequest::Flush,
+            Some(b'X') => PostgresRequest::Terminate,
+            Some(b'd') => PostgresRequest::CopyData(body.0),
+            Some(b'c') => PostgresRequest::CopyDone,
+            Some(b'f') => PostgresRequest::CopyFail(body.string()?),
+            Some(tag) => {
+                return Err(anyhow!(
+                    "unsupported Postgres message type: {:?}",
+                    char::from(tag)
+                ));
+            }
+        }))
+    }
+}
+
+impl Encoder<PostgresReply> for PostgresCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, reply: PostgresReply, dst: &mut BytesMut) -> Result<()> {
+        fn put_string(dst: &mut BytesMut, string: &str) {
+            dst.put_slice(string.as_bytes());
+            dst.put_u8(0);
+        }
+
+        dst.put_u8(reply.tag());
+        if let PostgresReply::SslRefused = reply {
+            return Ok(());
+        }
+
+        let start = dst.len();
+        // Placeholder for the length, which we fill in below
+        dst.put_i32(0);
+
+        match reply {
+            PostgresReply::SslRefused
+            | PostgresReply::ParseComplete
+            | PostgresReply::BindComplete
+            | PostgresReply::CloseComplete
+            | PostgresReply::NoData
+            | PostgresReply::EmptyQueryResponse
+            | PostgresReply::CopyDone => {}
+            PostgresReply::AuthenticationOk => dst.put_i32(0),
+            PostgresReply::ParameterStatus(name, value) => {
+                put_string(dst, name);
+                put_string(dst, value);
+            }
+            PostgresReply::BackendKeyData {
+                process_id,
+                secret_key,
+            } => {
+                dst.put_i32(process_id);
+                dst.put_i32(secret_key);
+            }
+            PostgresReply::ReadyForQuery(status) => dst.put_u8(status),
+            PostgresReply::ParameterDescription(types) => {
+                dst.put_i16(i16::try_from(types.len())?);
+                for ty in types {
+                    dst.put_u32(ty.oid());
+                }
+            }
+            PostgresReply::RowDescription(columns) => {
+                dst.put_i16(i16::try_from(columns.len())?);
+                for (name, ty, format) in columns {
+                    put_string(dst, name);
+                    // Table OID and attribute number
+                    dst.put_u32(0);
+                    dst.put_i16(0);
+                    dst.put_u32(ty.oid());
+                    dst.put_i16(ty.size());
+                    // Type modifier
+                    dst.put_i32(-1);
+                    dst.put_i16(format);
+                }
+            }
+            PostgresReply::DataRow(values) => {
+                dst.put_i16(i16::try_from(values.len())?);
+                for value in values {
+                    if let Some(value) = value {
+                        dst.put_i32(i32::try_from(value.len())?);
+                        dst.put_slice(&value);
+                    } else {
+                        dst.put_i32(-1);
+                    }
+                }
+            }
+            PostgresReply::CommandComplete(tag) => put_string(dst, &tag),
+            PostgresReply::ErrorResponse(PostgresError {
+                severity,
+                code,
+                message,
+            })
+            | PostgresReply::NoticeResponse(PostgresError {
+                severity,
+                code,
+                message,
+            }) => {
+                for (field, value) in [
+                    (b'S', severity),
+                    (b'V', severity),
+                    (b'C', code),
+                    (b'M', message.as_str()),
+                ] {
+                    dst.put_u8(field);
+                    put_string(dst, value);
+                }
+                dst.put_u8(0);
+            }
+            PostgresReply::CopyInResponse | PostgresReply::CopyOutResponse => {
+                // Text format with a single column
+                dst.put_i8(0);
+                dst.put_i16(1);
+                dst.put_i16(POSTGRES_TEXT);
+            }
+            PostgresReply::CopyData(data) => dst.put_slice(&data),
+        }
+
+        let length = i32::try_from(dst.len() - start)?;
+        dst[start..start + 4].copy_from_slice(&length.to_be_bytes());
+
+        Ok(())
+    }
+}
+
+/// The statements the Postgres fixture knows how to handle.
+#[derive(Clone, Debug)]
+enum PostgresStatement {
+    /// `SELECT $1::TEXT`
+    Echo,
+    /// `COPY <table> FROM STDIN`
+    CopyIn(String),
+    /// `COPY <table> TO STDOUT`
+    CopyOut(String),
+    /// `SELECT pg_sleep(<seconds>)`, which sends a notice once it starts and may then be interrupted by a cancel
+    /// request
+    Sleep(Duration),
+    /// `SELECT pg_backend_pid()`
+    BackendPid,
+    /// `SELECT pg_terminate_backend($1)`, which closes the specified connection once it's idle
+    TerminateBackend,
+    Begin,
+    Commit,
+    Rollback,
+}
+
+impl PostgresStatement {
+    fn parse(query: &str) -> Result<Self> {
+        let words = query.split_whitespace().collect::<Vec<_>>();
+
+        if let ["SELECT", call] = words.as_slice() {
+            if let Some(seconds) = call
+                .strip_prefix("pg_sleep(")
+                .and_then(|call| call.strip_suffix(')'))
+            {
+                return Ok(Self::Sleep(
+                    seconds
+                        .parse()
+                        .ok()
+                        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                        .ok_or_else(|| {
+                            PostgresError::new(
+                                "22P02",
+                                format!(
+                                    "invalid input syntax for type double precision: {seconds:?}"
+                                ),
+                            )
+                        })?,
+                ));
+            }
+        }
+
+        Ok(match words.as_slice() {
+            ["SELECT", "$1::TEXT"] => Self::Echo,
+            ["SELECT", "pg_backend_pid()"] => Self::BackendPid,
+            ["SELECT", "pg_terminate_backend($1)"] => Self::TerminateBackend,
+            ["COPY", table, "FROM", "STDIN"] => Self::CopyIn(table.to_string()),
+            ["COPY", table, "TO", "STDOUT"] => Self::CopyOut(table.to_string()),
+            ["BEGIN"] => Self::Begin,
+            ["COMMIT"] => Self::Commit,
+            ["ROLLBACK"] => Self::Rollback,
+            _ => {
+                return Err(PostgresError::new(
+                    "0A000",
+                    format!("unsupported statement: {query:?}"),
+                )
+                .into());
+            }
+        })
+    }
+
+    fn parameter_types(&self) -> Vec<PostgresType> {
+        match self {
+            Self::Echo => vec![PostgresType::Text],
+            Self::TerminateBackend => vec![PostgresType::Int4],
+            _ => Vec::new(),
+        }
+    }
+
+    fn columns(&self) -> Vec<(&'static str, PostgresType)> {
+        match self {
+            Self::Echo => vec![("text", PostgresType::Text)],
+            Self::Sleep(_) => vec![("pg_sleep", PostgresType::Void)],
+            Self::BackendPid => vec![("pg_backend_pid", PostgresType::Int4)],
+            Self::TerminateBackend => vec![("pg_terminate_backend", PostgresType::Bool)],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Describe the rows this statement returns, if any, using the specified result format codes.
+    fn row_description(&self, formats: &[i16]) -> PostgresReply {
+        let columns = self.columns();
+        if columns.is_empty() {
+            PostgresReply::NoData
+        } else {
+            PostgresReply::RowDescription(
+                columns
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, (name, ty))| (name, ty, postgres_format(formats, index)))
+                    .collect(),
+            )
+        }
+    }
+}
+
+/// A bound statement, ready to execute.
+#[derive(Clone)]
+struct PostgresPortal {
+    statement: PostgresStatement,
+    parameter_formats: Vec<i16>,
+    parameters: Vec<Option<Bytes>>,
+    result_formats: Vec<i16>,
+}
+
+impl PostgresPortal {
+    fn parameter(&self, index: usize) -> Result<&Bytes> {
+        Ok(self
+            .parameters
+            .get(index)
+            .ok_or_else(|| {
+                PostgresError::new("08P01", format!("missing parameter ${}", index + 1))
+            })?
+            .as_ref()
+            .ok_or_else(|| {
+                PostgresError::new(
+                    "22004",
+                    format!("unexpected null for parameter ${}", index + 1),
+                )
+            })?)
+    }
+
+    fn text_parameter(&self, index: usize) -> Result<String> {
+        // Text has the same representation in both text and binary format.
+        Ok(
+            String::from_utf8(self.parameter(index)?.to_vec()).map_err(|_| {
+                PostgresError::new("22021", "invalid byte sequence for encoding \"UTF8\"")
+            })?,
+        )
+    }
+
+    fn int4_parameter(&self, index: usize) -> Result<i32> {
+        let value = self.parameter(index)?;
+        let invalid = || {
+            PostgresError::new(
+                "22P02",
+                format!("invalid integer for parameter ${}", index + 1),
+            )
+        };
+
+        Ok(
+            if postgres_format(&self.parameter_formats, index) == POSTGRES_TEXT {
+                std::str::from_utf8(value)
+                    .ok()
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(invalid)?
+            } else {
+                i32::from_be_bytes(value[..].try_into().map_err(|_| invalid())?)
+            },
+        )
+    }
+
+    fn data_row(&self, values: &[PostgresValue]) -> PostgresReply {
+        PostgresReply::DataRow(
+            values
+                .iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    Some(value.encode(postgres_format(&self.result_formats, index)))
+                })
+                .collect(),
+        )
+    }
+}
+
+/// A connection's cancellation key, plus ways to interrupt its current query or close it.
+struct PostgresBackend {
+    secret_key: i32,
+    cancel: Arc<Notify>,
+    terminate: Arc<Notify>,
+}
+
+/// State shared by every connection to a `serve_postgres` fixture.
+#[derive(Default)]
+struct PostgresState {
+    /// Tables populated via `COPY ... FROM STDIN`
+    tables: StdMutex<HashMap<String, Bytes>>,
+    /// Connections which have completed startup, keyed by process ID
+    backends: StdMutex<HashMap<i32, PostgresBackend>>,
+    next_process_id: AtomicI32,
+}
+
+/// State for one connection to the Postgres fixture.
+struct PostgresConnection {
+    framed: Framed<TcpStream, PostgresCodec>,
+    state: Arc<PostgresState>,
+    process_id: i32,
+    /// Notified (via `Notify::notify_waiters`) when a client sends a cancel request with our process ID and secret
+    /// key.
+    ///
+    /// As in Postgres, a request only has an effect while a statement is running; one which arrives between queries
+    /// is ignored rather than canceling the next one.
+    cancel: Arc<Notify>,
+    /// Notified by `pg_terminate_backend` on another connection, causing us to close this one once it's idle.
+    terminate: Arc<Notify>,
+    statements: HashMap<String, PostgresStatement>,
+    portals: HashMap<String, PostgresPortal>,
+    /// Whether we're discarding messages until the next `Sync` due to an error.
+    failed: bool,
+    transaction: PostgresTransaction,
+}
+
+impl PostgresConnection {
+    async fn run(mut self) -> Result<()> {
+        loop {
+            match self.framed.try_next().await? {
+                Some(PostgresRequest::SslRequest) => {
+                    self.framed.send(PostgresReply::SslRefused).await?
+                }
+                Some(PostgresRequest::Startup) => break,
+                Some(PostgresRequest::Cancel {
+                    process_id,
+                    secret_key,
+                }) => {
+                    // Cancel requests arrive on their own connection, which we close without replying.
+                    if let Some(backend) = self.state.backends.lock().unwrap().get(&process_id) {
+                        if backend.secret_key == secret_key {
+                            backend.cancel.notify_waiters();
+                        }
+                    }
+                    return Ok(());
+                }
+                None => return Ok(()),
+                Some(request) => return Err(anyhow!("unexpected startup message: {request:?}")),
+            }
+        }
+
+        self.framed.feed(PostgresReply::AuthenticationOk).await?;
+        for (name, value) in [
+            ("server_version", "16.0"),
+            ("server_encoding", "UTF8"),
+            ("client_encoding", "UTF8"),
+            ("DateStyle", "ISO, MDY"),
+            ("integer_datetimes", "on"),
+        ] {
+            self.framed
+                .feed(PostgresReply::ParameterStatus(name, value))
+                .await?;
+        }
+        // `RandomState` gives us an unpredictable key without pulling in a random number generator.
+        let secret_key = RandomState::new().build_hasher().finish() as i32;
+        self.state.backends.lock().unwrap().insert(
+            self.process_id,
+            PostgresBackend {
+                secret_key,
+                cancel: self.cancel.clone(),
+                terminate: self.terminate.clone(),
+            },
+        );
+        self.framed
+            .feed(PostgresReply::BackendKeyData {
+                process_id: self.process_id,
+                secret_key,
+            })
+            .await?;
+        self.ready().await?;
+
+        loop {
+            // `None` here means we've been asked to terminate.  We check for that first so a client which keeps
+            // sending requests can't starve it.
+            let request = tokio::select! {
+                biased;
+                _ = self.terminate.notified() => None,
+                request = self.framed.try_next() => Some(request?),
+            };
+
+            let Some(request) = request else {
+                let error = PostgresError {
+                    severity: "FATAL",
+                    ..PostgresError::new(
+                        "57P01",
+                        "terminating connection due to administrator command",
+                    )
+                };
+                self.framed
+                    .send(PostgresReply::ErrorResponse(error))
+                    .await?;
+                break;
+            };
+
+            let Some(request) = request else {
+                break;
+            };
+
+            if self.failed && !matches!(request, PostgresRequest::Sync) {
+                continue;
+            }
+
+            if let PostgresRequest::Terminate = request {
+                break;
+            }
+
+            if let Err(e) = self.handle(request).await {
+                self.fail(e).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle a message from the client, returning a `PostgresError` (to be reported via `fail`) if it fails.
+    async fn handle(&mut self, request: PostgresRequest) -> Result<()> {
+        match request {
+            PostgresRequest::Query(query) => {
+                if let Err(e) = self.simple_query(&query).await {
+                    self.fail(e).await?;
+                }
+                // Unlike the extended protocol, the simple protocol doesn't wait for a `Sync`.
+                self.failed = false;
+                self.ready().await?;
+            }
+            PostgresRequest::Parse { name, query } => {
+                let statement = self.parse(&query)?;
+                self.statements.insert(name, statement);
+                self.framed.feed(PostgresReply::ParseComplete).await?;
+            }
+            PostgresRequest::Bind {
+                portal,
+                statement,
+                parameter_formats,
+                parameters,
+                result_formats,
+            } => {
+                let statement = self.statement(&statement)?.clone();
+                self.portals.insert(
+                    portal,
+                    PostgresPortal {
+                        statement,
+                        parameter_formats,
+                        parameters,
+                        result_formats,
+                    },
+                );
+                self.framed.feed(PostgresReply::BindComplete).await?;
+            }
+            PostgresRequest::Describe { kind: b'S', name } => {
+                let statement = self.statement(&name)?;
+                let parameters = PostgresReply::ParameterDescription(statement.parameter_types());
+                let rows = statement.row_description(&[]);
+                self.framed.feed(parameters).await?;
+                self.framed.feed(rows).await?;
+            }
+            PostgresRequest::Describe { kind: b'P', name } => {
+                let portal = self.portal(&name)?;
+                let rows = portal.statement.row_description(&portal.result_formats);
+                self.framed.feed(rows).await?;
+            }
+            PostgresRequest::Execute { portal } => {
+                let portal = self.portal(&portal)?.clone();
+                self.execute(&portal).await?;
+            }
+            PostgresRequest::Close { kind, name } => {
+                if kind == b'S' {
+                    self.statements.remove(&name);
+                } else {
+                    self.portals.remove(&name);
+                }
+                self.framed.feed(PostgresReply::CloseComplete).await?;
+            }
+            PostgresRequest::Sync => {
+                self.failed = false;
+                self.ready().await?;
+            }
+            PostgresRequest::Flush => self.framed.flush().await?,
+            request => return Err(anyhow!("unexpected message: {request:?}")),
+        }
+
+        Ok(())
+    }
+
+    async fn ready(&mut self) -> Result<()> {
+        self.framed
+            .send(PostgresReply::ReadyForQuery(self.transaction.status()))
+            .await
+    }
+
+    /// Report `error` to the client if it's a `PostgresError` and discard messages until the next `Sync`; otherwise,
+    /// return it.
+    ///
+    /// This also marks any transaction in progress as failed.
+    async fn fail(&mut self, error: Error) -> Result<()> {
+        let error = error.downcast::<PostgresError>()?;
+        self.failed = true;
+        if self.transaction == PostgresTransaction::Active {
+            self.transaction = PostgresTransaction::Failed;
+        }
+        self.framed.feed(PostgresReply::ErrorResponse(error)).await
+    }
+
+    /// Parse `query`, refusing anything but the end of the transaction block if the transaction has failed.
+    fn parse(&self, query: &str) -> Result<PostgresStatement> {
+        let statement = PostgresStatement::parse(query)?;
+
+        if self.transaction == PostgresTransaction::Failed
+            && !matches!(
+                statement,
+                PostgresStatement::Commit | PostgresStatement::Rollback
+            )
+        {
+            return Err(PostgresError::new(
+                "25P02",
+                "current transaction is aborted, commands ignored until end of transaction block",
+            )
+            .into());
+        }
+
+        Ok(statement)
+    }
+
+    /// Handle a `Query` message, i.e. the simple query protocol, which doesn't support parameters.
+    async fn simple_query(&mut self, query: &str) -> Result<()> {
+        if query.trim().is_empty() {
+            return self.framed.feed(PostgresReply::EmptyQueryResponse).await;
+        }
+
+        let statement = self.parse(query.trim().trim_end_matches(';'))?;
+        if !statement.parameter_types().is_empty() {
+            return Err(PostgresError::new(
+                "08P01",
+                format!("statement requires parameters: {query:?}"),
+            )
+            .into());
+        }
+
+        // The simple protocol describes the rows without being asked, but unlike `Describe` it omits `NoData`.
+        let rows = statement.row_description(&[]);
+        if let PostgresReply::RowDescription(_) = rows {
+            self.framed.feed(rows).await?;
+        }
+
+        self.execute(&PostgresPortal {
+            statement,
+            parameter_formats: Vec::new(),
+            parameters: Vec::new(),
+            result_formats: Vec::new(),
+        })
+        .await
+    }
+
+    fn statement(&self, name: &str) -> Result<&PostgresStatement> {
+        self.statements.get(name).ok_or_else(|| {
+            PostgresError::new(
+                "26000",
+                format!("prepared statement {name:?} does not exist"),
+            )
+            .into()
+        })
+    }
+
+    fn portal(&self, name: &str) -> Result<&PostgresPortal> {
+        self.portals.get(name).ok_or_else(|| {
+            PostgresError::new("34000", format!("portal {name:?} does not exist")).into()
+        })
+    }
+
+    async fn execute(&mut self, portal: &PostgresPortal) -> Result<()> {
+        let tag = match &portal.statement {
+            PostgresStatement::Echo => {
+                let row = portal.data_row(&[PostgresValue::Text(portal.text_parameter(0)?)]);
+                self.framed.feed(row).await?;
+                "SELECT 1".to_owned()
+            }
+            PostgresStatement::Sleep(duration) => {
+                // Start listening for cancel requests before telling the client we've started, so it can't send one
+                // we'll miss.
+                let cancel = pin!(self.cancel.notified());
+                self.framed
+                    .send(PostgresReply::NoticeResponse(PostgresError {
+                        severity: "NOTICE",
+                        ..PostgresError::new("00000", "sleeping")
+                    }))
+                    .await?;
+
+                let sleep = pin!(time::sleep(*duration));
+                if let Either::Right(_) = future::select(sleep, cancel).await {
+                    return Err(PostgresError::new(
+                        "57014",
+                        "canceling statement due to user request",
+                    )
+                    .into());
+                }
+
+                self.framed
+                    .feed(portal.data_row(&[PostgresValue::Void]))
+                    .await?;
+                "SELECT 1".to_owned()
+            }
+            PostgresStatement::BackendPid => {
+                self.framed
+                    .feed(portal.data_row(&[PostgresValue::Int4(self.process_id)]))
+                    .await?;
+                "SELECT 1".to_owned()
+            }
+            PostgresStatement::TerminateBackend => {
+                let process_id = portal.int4_parameter(0)?;
+                let terminated = self
+                    .state
+                    .backends
+                    .lock()
+                    .unwrap()
+                    .get(&process_id)
+                    .map(|backend| backend.terminate.notify_one())
+                    .is_some();
+
+                self.framed
+                    .feed(portal.data_row(&[PostgresValue::Bool(terminated)]))
+                    .await?;
+                "SELECT 1".to_owned()
+            }
+            PostgresStatement::Begin => {
+                // As in Postgres, `BEGIN` inside a transaction block is allowed (with a warning, which we skip).
+                self.transaction = PostgresTransaction::Active;
+                "BEGIN".to_owned()
+            }
+            PostgresStatement::Commit => {
+                let tag = if self.transaction == PostgresTransaction::Failed {
+                    "ROLLBACK"
+                } else {
+                    "COMMIT"
+                };
+                self.transaction = PostgresTransaction::Idle;
+                tag.to_owned()
+            }
+            PostgresStatement::Rollback => {
+                self.transaction = PostgresTransaction::Idle;
+                "ROLLBACK".to_owned()
+            }
+            PostgresStatement::CopyIn(table) => {
+                self.framed.send(PostgresReply::CopyInResponse).await?;
+
+                let mut data = BytesMut::new();
+                loop {
+                    match self.framed.try_next().await? {
+                        Some(PostgresRequest::CopyData(chunk)) => data.extend_from_slice(&chunk),
+                        Some(PostgresRequest::CopyDone) => break,
+                        Some(PostgresRequest::CopyFail(message)) => {
+                            return Err(PostgresError::new(
+                                "57014",
+                                format!("COPY from stdin failed: {message}"),
+                            )
+                            .into());
+                        }
+                        // Per the protocol, these are ignored during COPY IN.
+                        Some(PostgresRequest::Sync | PostgresRequest::Flush) => {}
+                        Some(request) => {
+                            return Err(anyhow!("unexpected message during COPY: {request:?}"));
+                        }
+                        None => return Err(anyhow!("connection closed during COPY")),
+                    }
+                }
+
+                let rows = data.iter().filter(|&&byte| byte == b'\n').count();
+                self.state
+                    .tables
+                    .lock()
+                    .unwrap()
+                    .insert(table.clone(), data.freeze());
+                format!("COPY {rows}")
+            }
+            PostgresStatement::CopyOut(table) => {
+                let data = self
+                    .state
+                    .tables
+                    .lock()
+                    .unwrap()
+                    .get(table)
+                    .cloned()
+                    .ok_or_else(|| {
+                        PostgresError::new("42P01", format!("relation {table:?} does not exist"))
+                    })?;
+
+                self.framed.feed(PostgresReply::CopyOutResponse).await?;
+                // `feed` flushes whenever the write buffer fills up, so this is subject to backpressure from the
+                // client.
+                for offset in (0..data.len()).step_by(POSTGRES_COPY_CHUNK_SIZE) {
+                    let end = data.len().min(offset + POSTGRES_COPY_CHUNK_SIZE);
+                    self.framed
+                        .feed(PostgresReply::CopyData(data.slice(offset..end)))
+                        .await?;
+                }
+                self.framed.feed(PostgresReply::CopyDone).await?;
+
+                format!(
+                    "COPY {}",
+                    data.iter().filter(|&&byte| byte == b'\n').count()
+                )
+            }
+        };
+
+        self.framed
+            .feed(PostgresReply::CommandComplete(tag))
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl Drop for PostgresConnection {
+    fn drop(&mut self) {
+        self.state.backends.lock().unwrap().remove(&self.process_id);
+    }
+}
+
+pub async fn serve_postgres(
+    address: SocketAddr,
+) -> Result<(impl Future<Output = Result<()>>, SocketAddr)> {
+    let listener = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Unable to listen on {address}"))?;
+
+    let address = listener.local_addr()?;
+    let state = Arc::new(PostgresState::default());
+
+    Ok((
+        async move {
+            loop {
+                let (stream, _) = listener.accept().await?;
+
+                task::spawn(
+                    PostgresConnection {
+                        framed: Framed::new(stream, PostgresCodec::default()),
+                        state: state.clone(),
+                        process_id: state.next_process_id.fetch_add(1, Ordering::Relaxed) + 1,
+                        cancel: Arc::new(Notify::new()),
+                        terminate: Arc::new(Notify::new()),
+                        statements: HashMap::new(),
+                        portals: HashMap::new(),
+                        failed: false,
+                        transaction: PostgresTransaction::Idle,
+                    }
+                    .run()
+                    .map(|result| {
+                        if let Err(e) = result {
+                            log::warn!("error handling connection: {e:?}");
+                        }
+                    }),
+                );
+            }
+        }
+        .boxed(),
+        address,
+    ))
+}
+
+/// Default for `RedisCodec::max_frame_size`, matching Redis' own `proto-max-bulk-len` default.
+pub const DEFAULT_REDIS_MAX_FRAME_SIZE: usize = 512 * 1024 * 1024;
+
+/// Codec for the Redis fixture.
+///
+/// Replies start out encoded as RESP2, converting RESP3-only types the same way Redis does (e.g. maps become flat
+/// arrays and push frames become arrays), until the flag returned by `RedisCodec::resp3` is set.  Incoming frames
+/// are decoded in place using the streaming decoder, which also handles RESP3 streamed strings and aggregates (e.g.
+/// `$?`).  Any single frame (including the sum of a streamed frame's chunks) larger than `max_frame_size` bytes is
+/// rejected.
+pub struct RedisCodec {
+    max_frame_size: usize,
+    /// A partially-received streamed frame, if any, plus the number of bytes received for it so far.
+    streamed: Option<(StreamedFrame, usize)>,
+    resp3: Arc<AtomicBool>,
+}
+
+impl RedisCodec {
+    pub fn new(max_frame_size: usize) -> Self {
+        Self {
+            max_frame_size,
+            streamed: None,
+            resp3: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Return a flag which switches the encoding of subsequent replies between RESP2 (`false`) and RESP3 (`true`).
+    ///
+    /// Clients send the same commands regardless of protocol version, so decoding is unaffected.
+    pub fn resp3(&self) -> Arc<AtomicBool> {
+        self.resp3.clone()
+    }
+
+    fn check_size(&self, size: usize) -> Result<()> {
+        if size > self.max_frame_size {
+            Err(anyhow!(
+                "frame size of at least {size} bytes exceeds maximum of {} bytes",
+                self.max_frame_size
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for RedisCodec {
+    fn default() -> Self {
+        Self::new(DEFAULT_REDIS_MAX_FRAME_SIZE)
+    }
+}
+
+impl Decoder for RedisCodec {
+    type Item = Frame;
+    type Error = anyhow::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Frame>> {
+        loop {
+            let received = self.streamed.as_ref().map_or(0, |(_, size)| *size);
+
+            let Some((frame, length, _)) =
+                decode::streaming::decode_mut(src).map_err(|e| anyhow!("{e}"))?
+            else {
+                // Don't wait forever for the rest of an oversized frame.
+                self.check_size(received + src.len())?;
+                return Ok(None);
+            };
+
+            let size = received + length;
+            self.check_size(size)?;
+
+            match (frame, self.streamed.take()) {
+                (DecodedFrame::Complete(frame), None) => return Ok(Some(frame)),
+                (DecodedFrame::Complete(frame), Some((mut streamed, _))) => {
+                    streamed.add_frame(frame);
+                    if streamed.is_finished() {
+                        return Ok(Some(streamed.into_frame().map_err(|e| anyhow!("{e}"))?));
+                    }
+                    self.streamed = Some((streamed, size));
+                }
+                (DecodedFrame::Streaming(streamed), None) => self.streamed = Some((streamed, size)),
+                (DecodedFrame::Streaming(_), Some(_)) => {
+                    return Err(anyhow!("nested streamed frames are not supported"));
+                }
+            }
+        }
+    }
+}
+
+impl Encoder<Frame> for RedisCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, frame: Frame, dst: &mut BytesMut) -> Result<()> {
+        if self.resp3.load(Ordering::Relaxed) {
+            encode::complete::encode_bytes(dst, &frame).map_err(|e| anyhow!("{e}"))?;
+        } else {
+            resp2::encode::encode_bytes(dst, &to_resp2(frame)?).map_err(|e| anyhow!("{e}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Convert a RESP3 reply to its RESP2 equivalent.
+fn to_resp2(frame: Frame) -> Result<Resp2Frame> {
+    let array = |frames: Vec<Frame>| -> Result<Resp2Frame> {
+        Ok(Resp2Frame::Array(
+            frames.into_iter().map(to_resp2).collect::<Result<_>>()?,
+        ))
+    };
+
+    Ok(match frame {
+        Frame::SimpleString { data, .. } => Resp2Frame::SimpleString(data),
+        Frame::SimpleError { data, .. } => Resp2Frame::Error(data),
+        Frame::BlobError { data, .. } => {
+            Resp2Frame::Error(String::from_utf8_lossy(&data).into_owned().into())
+        }
+        Frame::BlobString { data, .. }
+        | Frame::BigNumber { data, .. }
+        | Frame::VerbatimString { data, .. } => Resp2Frame::BulkString(data),
+        Frame::Number { data, .. } => Resp2Frame::Integer(data),
+        Frame::Boolean { data, .. } => Resp2Frame::Integer(data.into()),
+        Frame::Double { data, .. } => Resp2Frame::BulkString(data.to_string().into()),
+        Frame::Null => Resp2Frame::Null,
+        Frame::Array { data, .. } | Frame::Push { data, .. } => array(data)?,
+        Frame::Set { data, .. } => array(data.into_iter().collect())?,
+        Frame::Map { data, .. } => array(data.into_iter().flat_map(|(k, v)| [k, v]).collect())?,
+        _ => return Err(anyhow!("unable to convert frame to RESP2: {frame:?}")),
+    })
+}
+
+/// Pub/sub state shared by every connection to a `serve_redis_with` fixture.
+#[derive(Default)]
+pub struct RedisBroker {
+    /// Push frame senders for each subscribed connection, keyed by channel and then by connection ID.
+    subscribers: StdMutex<HashMap<Bytes, HashMap<usize, mpsc::UnboundedSender<Frame>>>>,
+    next_id: AtomicUsize,
+    changed: Notify,
+}
+
+impl RedisBroker {
+    /// Send `message` to every connection subscribed to `channel`, returning how many there were.
+    pub fn publish(&self, channel: &[u8], message: &[u8]) -> usize {
+        let subscribers = self.subscribers.lock().unwrap();
+        let Some(subscribers) = subscribers.get(channel) else {
+            return 0;
+        };
+
+        for pushes in subscribers.values() {
+            _ = pushes.unbounded_send(push([blob(b"message"), blob(channel), blob(message)]));
+        }
+
+        subscribers.len()
+    }
+
+    /// Return the number of connections currently subscribed to `channel`.
+    pub fn subscriber_count(&self, channel: &[u8]) -> usize {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .get(channel)
+            .map_or(0, HashMap::len)
+    }
+
+    /// Wait until at least `count` connections are subscribed to `channel`.
+    pub async fn wait_for_subscribers(&self, channel: &[u8], count: usize) {
+        loop {
+            let changed = self.changed.notified();
+            if self.subscriber_count(channel) >= count {
+                break;
+            }
+            changed.await;
+        }
+    }
+
+    /// Subscribe connection `id` to `channel`, sending any messages published afterward to `pushes`.
+    fn subscribe(&self, id: usize, channel: Bytes, pushes: &mpsc::UnboundedSender<Frame>) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .entry(channel)
+            .or_default()
+            .insert(id, pushes.clone());
+        self.changed.notify_waiters();
+    }
+
+    fn unsubscribe(&self, id: usize, channel: &[u8]) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if let Some(channel_subscribers) = subscribers.get_mut(channel) {
+            channel_subscribers.remove(&id);
+            if channel_subscribers.is_empty() {
+                subscribers.remove(channel);
+            }
+        }
+        drop(subscribers);
+        self.changed.notify_waiters();
+    }
+}
+
+fn blob(data: &[u8]) -> Frame {
+    Frame::BlobString {
+        data: Bytes::copy_from_slice(data),
+        attributes: None,
+    }
+}
+
+fn simple(data: &[u8]) -> Frame {
+    Frame::SimpleString {
+        data: Bytes::copy_from_slice(data),
+        attributes: None,
+    }
+}
+
+fn number(data: usize) -> Frame {
+    Frame::Number {
+        data: data as i64,
+        attributes: None,
+    }
+}
+
+fn push<const N: usize>(data: [Frame; N]) -> Frame {
+    Frame::Push {
+        data: data.into(),
+        attributes: None,
+    }
+}
+
+/// State for one connection to the Redis fixture.
+struct RedisConnection {
+    id: usize,
+    broker: Arc<RedisBroker>,
+    /// Messages published to subscribed channels, to be sent to the client between replies.
+    pushes: mpsc::UnboundedSender<Frame>,
+    channels: HashSet<Bytes>,
+    /// See `RedisCodec::resp3`.
+    resp3: Arc<AtomicBool>,
+}
+
+impl RedisConnection {
+    fn hello(&self) -> Frame {
+        let version = if self.resp3.load(Ordering::Relaxed) {
+            3
+        } else {
+            2
+        };
+
+        Frame::Map {
+            data: [
+                (blob(b"server"), blob(b"redis")),
+                (blob(b"version"), blob(b"7.2.0")),
+                (blob(b"proto"), number(version)),
+                (blob(b"id"), number(self.id)),
+                (blob(b"mode"), blob(b"standalone")),
+                (blob(b"role"), blob(b"master")),
+                (
+                    blob(b"modules"),
+                    Frame::Array {
+                        data: Vec::new(),
+                        attributes: None,
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            attributes: None,
+        }
+    }
+
+    /// Handle a command from the client, returning the replies to send, in order.
+    ///
+    /// Most commands have exactly one reply, but `SUBSCRIBE` and `UNSUBSCRIBE` are answered with a confirmation push
+    /// frame per channel.
+    fn handle(&mut self, frame: Frame) -> Result<Vec<Frame>> {
+        let unexpected = || Err(anyhow!("don't know how to handle frame: {frame:?}"));
+
+        let Frame::Array { data, .. } = &frame else {
+            return unexpected();
+        };
+
+        let Some(args) = data
+            .iter()
+            .map(|arg| match arg {
+                Frame::BlobString { data, .. } => Some(data.clone()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+        else {
+            return unexpected();
+        };
+
+        Ok(vec![
+            match args.iter().map(Bytes::deref).collect::<Vec<_>>().as_slice() {
+                [b"PING"] => simple(b"PONG"),
+                [b"GET", b"foo"] => blob(b"bar"),
+                [b"GET", _] => Frame::Null,
+                [b"SET", b"foo", b"bar"] => simple(b"OK"),
+                [b"COMMAND", b"DOCS"] => Frame::Map {
+                    data: HashMap::new(),
+                    attributes: None,
+                },
+                [b"CLIENT", b"SETINFO", _, _] => simple(b"OK"),
+                [b"HELLO", version @ ..] if matches!(version, [] | [b"2" | b"3"]) => {
+                    if let [version] = version {
+                        self.resp3.store(*version == b"3", Ordering::Relaxed);
+                    }
+                    self.hello()
+                }
+                [b"PUBLISH", channel, message] => number(self.broker.publish(channel, message)),
+                [b"SUBSCRIBE", channels @ ..] if !channels.is_empty() => {
+                    return Ok(channels
+                        .iter()
+                        .map(|&channel| {
+                            let channel = Bytes::copy_from_slice(channel);
+                            self.channels.insert(channel.clone());
+                            self.broker
+                                .subscribe(self.id, channel.clone(), &self.pushes);
+                            push([
+                                blob(b"subscribe"),
+                                blob(&channel),
+                                number(self.channels.len()),
+                            ])
+                        })
+                        .collect());
+                }
+                [b"UNSUBSCRIBE", channels @ ..] => {
+                    let channels = if channels.is_empty() {
+                        self.channels.iter().cloned().collect::<Vec<_>>()
+                    } else {
+                        channels
+                            .iter()
+                            .map(|&channel| Bytes::copy_from_slice(channel))
+                            .collect()
+                    };
+
+                    if channels.is_empty() {
+                        return Ok(vec![push([blob(b"unsubscribe"), Frame::Null, number(0)])]);
+                    }
+
+                    return Ok(channels
+                        .into_iter()
+                        .map(|channel| {
+                            self.channels.remove(&channel);
+                            self.broker.unsubscribe(self.id, &channel);
+                            push([
+                                blob(b"unsubscribe"),
+                                blob(&channel),
+                                number(self.channels.len()),
+                            ])
+                        })
+                        .collect());
+                }
+                _ => return unexpected(),
+            },
+        ])
+    }
+}
+
+impl Drop for RedisConnection {
+    fn drop(&mut self) {
+        for channel in &self.channels {
+            self.broker.unsubscribe(self.id, channel);
+        }
+    }
+}
+
+pub async fn serve_redis(
+    address: SocketAddr,
+) -> Result<(impl Future<Output = Result<()>>, SocketAddr)> {
+    serve_redis_with(address, Arc::default()).await
+}
+
+/// Like `serve_redis`, but with pub/sub state shared with the caller via `broker`, allowing the caller to publish
+/// messages to connected clients.
+pub async fn serve_redis_with(
+    address: SocketAddr,
+    broker: Arc<RedisBroker>,
+) -> Result<(impl Future<Output = Result<()>>, SocketAddr)> {
+    let listener = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Unable to listen on {address}"))?;
+
+    let address = listener.local_addr()?;
+
+    Ok((
+        async move {
+            loop {
+                let (stream, _) = listener.accept().await?;
+                let broker = broker.clone();
+
+                task::spawn(
+                    async move {
+                        let codec = RedisCodec::default();
+                        let resp3 = codec.resp3();
+                        let (tx, mut rx) = Framed::new(stream, codec).split();
+                        let tx = Arc::new(AsyncMutex::new(tx));
+
+                        let (pushes, mut push_rx) = mpsc::unbounded();
+                        let mut connection = RedisConnection {
+                            id: broker.next_id.fetch_add(1, Ordering::Relaxed),
+                            broker,
+                            pushes,
+                            channels: HashSet::new(),
+                            resp3,
+                        };
+
+                        // Forward published messages to the client as they arrive, interleaved with replies to
+                        // commands.
+                        let forward = task::spawn({
+                            let tx = tx.clone();
+                            async move {
+                                while let Some(frame) = push_rx.next().await {
+                                    tx.lock().await.send(frame).await?;
+                                }
+                                Ok::<_, Error>(())
+                            }
+                        });
+
+                        let result = async {
+                            while let Some(frame) = rx.try_next().await? {
+                                // Hold the lock while handling the command so that no message published to a
+                                // channel it subscribes to can overtake the confirmation.
+                                let mut tx = tx.lock().await;
+                                for reply in connection.handle(frame)? {
+                                    tx.send(reply).await?;
+                                }
+                            }
+                            Ok::<_, Error>(())
+                        }
+                        .await;
+
+                        // Dropping the connection unsubscribes it from everything, which closes the push channel and
+                        // lets `forward` finish.
+                        drop(connection);
+                        forward.await??;
+
+                        result
+                    }
+                    .map(|result| {
+                        if let Err(e) = result {
+                            log::warn!("error handling connection: {e:?}");
+                        }
+                    }),
+                );
+            }
+        }
+        .boxed(),
+        address,
+    ))
+}
+
+#[cfg(test)]
+mod harness;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::harness::{
+            Limits, SocketsCtx, build_component, build_python_component, instantiate_scenarios,
+            run, scenarios::exports::test::test::scenarios::Config as ScenarioConfig, shared,
+            spawn_server, store, wasi,
+        },
+        super::*,
+        anyhow::anyhow,
+        proptest::{collection::vec, prelude::*, sample::Index},
+        std::{
+            net::{Ipv4Addr, SocketAddrV6},
+            time::Duration,
+        },
+        wasmtime::{
+            Instance, Module, Store, Trap,
+            component::{ResourceTable, ResourceTableError},
+        },
+    };
+
+    async fn test_postgres(
+        src_path: &str,
+        name: &str,
+        address: SocketAddr,
+        hostname: Option<&str>,
+        args: &[&str],
+    ) -> Result<()> {
+        test_with(
+            hostname,
+            args,
+            &Limits::default(),
+            &build_component(src_path, name).await?,
+            async move { serve_postgres(address).await },
+        )
+        .await
+    }
+
+    fn redis_frame() -> impl Strategy<Value = Frame> {
+        let leaf = prop_oneof![
+            vec(any::<u8>(), 0..1024).prop_map(|data| Frame::BlobString {
+                data: data.into(),
+                attributes: None
+            }),
+            "[a-zA-Z0-9 ]{0,64}".prop_map(|data| Frame::SimpleString {
+                data: data.into(),
+                attributes: None
+            }),
+            any::<i64>().prop_map(|data| Frame::Number {
+                data,
+                attributes: None
+            }),
+            any::<bool>().prop_map(|data| Frame::Boolean {
+                data,
+                attributes: None
+            }),
+            Just(Frame::Null),
+        ];
+
+        leaf.prop_recursive(3, 64, 8, |inner| {
+            prop_oneof![
+                vec(inner.clone(), 0..8).prop_map(|data| Frame::Array {
+                    data,
+                    attributes: None
+                }),
+                vec(inner, 1..8).prop_map(|data| Frame::Push {
+                    data,
+                    attributes: None
+                }),
+            ]
+        })
+    }
+
+    /// Feed `input` to `codec` in chunks delimited by `splits`, returning every frame decoded.
+    fn decode_split(codec: &mut RedisCodec, input: &[u8], splits: &[Index]) -> Result<Vec<Frame>> {
+        let mut splits = splits
+            .iter()
+            .map(|index| index.index(input.len() + 1))
+            .chain([input.len()])
+            .collect::<Vec<_>>();
+        splits.sort_unstable();
+
+        let mut buffer = BytesMut::new();
+        let mut frames = Vec::new();
+        let mut offset = 0;
+        for split in splits {
+            buffer.extend_from_slice(&input[offset..split]);
+            offset = split;
+            while let Some(frame) = codec.decode(&mut buffer)? {
+                frames.push(frame);
+            }
+        }
+
+        if buffer.is_empty() {
+            Ok(frames)
+        } else {
+            Err(anyhow!("{} trailing bytes left undecoded", buffer.len()))
+        }
+    }
+
+    #[test]
+    fn redis_codec_resp2() -> Result<()> {
+        let mut codec = RedisCodec::default();
+        let mut output = BytesMut::new();
+        for frame in [
+            push([blob(b"message"), blob(b"news"), blob(b"hi")]),
+            Frame::Null,
+            Frame::Boolean {
+                data: true,
+                attributes: None,
+            },
+            Frame::Map {
+                data: [(blob(b"proto"), number(2))].into_iter().collect(),
+                attributes: None,
+            },
+        ] {
+            codec.encode(frame, &mut output)?;
+        }
+
+        assert_eq!(
+            &b"*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n$-1\r\n:1\r\n*2\r\n$5\r\nproto\r\n:2\r\n"[..],
+            &output[..]
+        );
+
+        codec.resp3().store(true, Ordering::Relaxed);
+        output.clear();
+        codec.encode(Frame::Null, &mut output)?;
+
+        assert_eq!(&b"_\r\n"[..], &output[..]);
+
+        Ok(())
+    }
+
+    /// Check that pub/sub confirmations are sent in order with replies to the commands pipelined around them.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn redis_pipelined_subscribe() -> Result<()> {
+        let (_tx, address) = spawn_server(serve_redis((Ipv4Addr::LOCALHOST, 0).into())).await?;
+
+        let mut stream = TcpStream::connect(address).await?;
+        stream
+            .write_all(
+                b"*2\r\n$9\r\nSUBSCRIBE\r\n$4\r\nnews\r\n*1\r\n$4\r\nPING\r\n\
+                  *2\r\n$11\r\nUNSUBSCRIBE\r\n$4\r\nnews\r\n*1\r\n$4\r\nPING\r\n",
+            )
+            .await?;
+
+        let expected = b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n+PONG\r\n\
+                         *3\r\n$11\r\nunsubscribe\r\n$4\r\nnews\r\n:0\r\n+PONG\r\n";
+        let mut received = vec![0; expected.len()];
+        time::timeout(Duration::from_secs(10), stream.read_exact(&mut received)).await??;
+
+        assert_eq!(
+            String::from_utf8_lossy(expected),
+            String::from_utf8_lossy(&received)
+        );
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn redis_codec_round_trip(
+            frames in vec(redis_frame(), 1..8),
+            splits in vec(any::<Index>(), 0..16),
+        ) {
+            let mut codec = RedisCodec::default();
+            codec.resp3().store(true, Ordering::Relaxed);
+            let mut input = BytesMut::new();
+            for frame in &frames {
+                codec.encode(frame.clone(), &mut input).unwrap();
+            }
+
+            prop_assert_eq!(frames, decode_split(&mut codec, &input, &splits).unwrap());
+        }
+
+        #[test]
+        fn redis_codec_streamed_string(
+            chunks in vec(vec(any::<u8>(), 1..64), 0..8),
+            splits in vec(any::<Index>(), 0..16),
+        ) {
+            let mut input = b"$?\r\n".to_vec();
+            for chunk in &chunks {
+                input.extend(format!(";{}\r\n", chunk.len()).as_bytes());
+                input.extend(chunk);
+                input.extend(b"\r\n");
+            }
+            input.extend(b";0\r\n");
+
+            prop_assert_eq!(
+                vec![Frame::BlobString {
+                    data: chunks.concat().into(),
+                    attributes: None
+                }],
+                decode_split(&mut RedisCodec::default(), &input, &splits).unwrap()
+            );
+        }
+
+        #[test]
+        fn redis_codec_max_frame_size(
+            size in 0_usize..4096,
+            splits in vec(any::<Index>(), 0..16),
+        ) {
+            let frame = Frame::BlobString {
+                data: vec![b'x'; size].into(),
+                attributes: None,
+            };
+            let mut input = BytesMut::new();
+            let mut codec = RedisCodec::default();
+            codec.resp3().store(true, Ordering::Relaxed);
+            codec.encode(frame.clone(), &mut input).unwrap();
+
+            prop_assert_eq!(
+                vec![frame],
+                decode_split(&mut RedisCodec::new(input.len()), &input, &splits).unwrap()
+            );
+            prop_assert!(decode_split(&mut RedisCodec::new(input.len() - 1), &input, &splits).is_err());
+        }
+    }
+
+    async fn test_redis(
+        src_path: &str,
+        name: &str,
+        address: SocketAddr,
+        hostname: Option<&str>,
+    ) -> Result<()> {
+        test(
+            hostname,
+            &build_component(src_path, name).await?,
+            async move { serve_redis(address).await },
+        )
+        .await
+    }
+
+    async fn test_echo(
+        src_path: &str,
+        name: &str,
+        address: SocketAddr,
+        hostname: Option<&str>,
+    ) -> Result<()> {
+        test(
+            hostname,
+            &build_component(src_path, name).await?,
+            async move { serve_echo(address).await },
+        )
+        .await
+    }
+
+    async fn test_tcp_options(address: SocketAddr) -> Result<()> {
+        test_with(
+            None,
+            &["tcp-options"],
+            &Limits::default(),
+            &build_component("../client", "sockets-client").await?,
+            async move { serve_tcp_options(address).await },
+        )
+        .await
+    }
+
+    async fn test_http(address: SocketAddr) -> Result<()> {
+        test_with(
+            None,
+            &["http"],
+            &Limits::default(),
+            &build_component("../client-tokio", "sockets-client-tokio").await?,
+            async move { serve_http(address).await },
+        )
+        .await
+    }
+
+    async fn test_websocket(address: SocketAddr) -> Result<()> {
+        test_with(
+            None,
+            &["websocket"],
+            &Limits::default(),
+            &build_component("../client-tokio", "sockets-client-tokio").await?,
+            async move { serve_websocket(address).await },
+        )
+        .await
+    }
+
+    /// Find an IPv6 link-local address assigned to this machine and the index of its interface, preferring the
+    /// loopback interface.
+    fn link_local() -> Result<Option<(Ipv6Addr, u32)>> {
+        // Each line looks like `fe800000000000000000000000000001 01 40 20 80 lo`: address, interface index, prefix
+        // length, scope, flags, and interface name, all but the last in hex.
+        let mut candidates = fs::read_to_string("/proc/net/if_inet6")?
+            .lines()
+            .filter_map(|line| {
+                let fields = line.split_whitespace().collect::<Vec<_>>();
+                let &[address, index, _, scope, flags, name] = fields.as_slice() else {
+                    return None;
+                };
+                let flags = u32::from_str_radix(flags, 16).ok()?;
+                // Skip addresses which aren't link-scoped, are still undergoing duplicate address detection, or
+                // failed it.
+                (scope == "20" && flags & (0x40 | 0x08) == 0).then_some(())?;
+                Some((
+                    name == "lo",
+                    Ipv6Addr::from(u128::from_str_radix(address, 16).ok()?),
+                    u32::from_str_radix(index, 16).ok()?,
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        candidates.sort_by_key(|&(loopback, ..)| !loopback);
+
+        Ok(candidates
+            .into_iter()
+            .next()
+            .map(|(_, address, index)| (address, index)))
+    }
+
+    async fn test_scope_id(src_path: &str, name: &str) -> Result<()> {
+        let (ip, scope_id) = link_local()?.ok_or_else(|| {
+            anyhow!("scope ID tests require a link-local IPv6 address on this machine")
+        })?;
+
+        test_with(
+            None,
+            &["scope-id"],
+            &Limits::default(),
+            &build_component(src_path, name).await?,
+            serve_echo(SocketAddrV6::new(ip, 0, 0, scope_id).into()),
+        )
+        .await
+    }
+
+    /// Find a port which is free for both TCP and UDP on `ip`.
+    fn free_port(ip: IpAddr) -> Result<u16> {
+        loop {
+            let port = std::net::TcpListener::bind((ip, 0))?.local_addr()?.port();
+            if std::net::UdpSocket::bind((ip, port)).is_ok() {
+                break Ok(port);
+            }
+        }
+    }
+
+    async fn test_bind(address: SocketAddr) -> Result<()> {
+        let port = free_port(address.ip())?;
+        let port_arg = port.to_string();
+        let (tx, mut rx) = mpsc::unbounded();
+
+        test_with(
+            None,
+            &["bind", port_arg.as_str()],
+            &Limits::default(),
+            &build_component("../client", "sockets-client").await?,
+            async move { serve_peers(address, tx).await },
+        )
+        .await?;
+
+        let peers = iter::from_fn(|| rx.try_next().ok().flatten()).collect::<Vec<_>>();
+        let [("tcp", bound), ("tcp", ephemeral), ("udp", udp)] = peers.as_slice() else {
+            return Err(anyhow!("unexpected peers: {peers:?}"));
+        };
+
+        assert_eq!(port, bound.port());
+        assert_ne!(port, ephemeral.port());
+        assert_eq!(port, udp.port());
+
+        for peer in [bound, ephemeral, udp] {
+            assert_eq!(address.ip(), peer.ip());
+        }
+
+        Ok(())
+    }
+
+    async fn test_readiness(address: SocketAddr) -> Result<()> {
+        let (tx, mut rx) = mpsc::unbounded();
+
+        test_with(
+            None,
+            &["readiness"],
+            &Limits::default(),
+            &build_component("../client", "sockets-client").await?,
+            async move { serve_readiness(address, tx).await },
+        )
+        .await?;
+
+        // The fixture reports each command as it handles it, which may be after the guest has exited.
+        for expected in ["write 5", "write 1", "drain"] {
+            assert_eq!(
+                Some(expected),
+                time::timeout(Duration::from_secs(10), rx.next())
+                    .await?
+                    .as_deref()
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn test_udp_options(address: SocketAddr) -> Result<()> {
+        test_with(
+            None,
+            &["udp-options"],
+            &Limits::default(),
+            &build_component("../client", "sockets-client").await?,
+            async move { serve_udp_echo(address).await },
+        )
+        .await
+    }
+
+    async fn test_half_close(address: SocketAddr) -> Result<()> {
+        let (tx, mut rx) = mpsc::unbounded();
+
+        test_with(
+            None,
+            &["half-close"],
+            &Limits::default(),
+            &build_component("../client", "sockets-client").await?,
+            async move { serve_half_close(address, tx).await },
+        )
+        .await?;
+
+        // The fixture may still be draining the second connection after the guest exits, so wait for each payload
+        // rather than assuming it has already arrived.
+        for expected in [
+            b"So rested he by the Tumtum tree".as_slice(),
+            b"Long time the manxome foe he sought",
+        ] {
+            assert_eq!(
+                Some(expected.to_vec()),
+                time::timeout(Duration::from_secs(10), rx.next()).await?
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn test_python_echo(
+        src_paths: &[&str],
+        address: SocketAddr,
+        hostname: Option<&str>,
+    ) -> Result<()> {
+        test(
+            hostname,
+            &build_python_component(src_paths, "test:test/command").await?,
+            async move { serve_echo(address).await },
+        )
+        .await
+    }
+
+    async fn test_python_redis(
+        src_paths: &[&str],
+        address: SocketAddr,
+        hostname: Option<&str>,
+        args: &[&str],
+    ) -> Result<()> {
+        test_with(
+            hostname,
+            args,
+            &Limits::default(),
+            &build_python_component(src_paths, "wasi:cli/command@0.2.0").await?,
+            async move { serve_redis(address).await },
+        )
+        .await
+    }
+
+    async fn test(
+        hostname: Option<&str>,
+        component: &[u8],
+        serve: impl Future<
+            Output = Result<(
+                impl Future<Output = Result<()>> + Unpin + Send + 'static,
+                SocketAddr,
+            )>,
+        >,
+    ) -> Result<()> {
+        test_with(hostname, &[], &Limits::default(), component, serve).await
+    }
+
+    async fn test_with(
+        hostname: Option<&str>,
+        args: &[&str],
+        limits: &Limits,
+        component: &[u8],
+        serve: impl Future<
+            Output = Result<(
+                impl Future<Output = Result<()>> + Unpin + Send + 'static,
+                SocketAddr,
+            )>,
+        >,
+    ) -> Result<()> {
+        run_with(hostname, args, limits, component, serve)
+            .await
+            .map(drop)
+    }
+
+    /// Like `test_with`, but returns the store so the caller can inspect it afterward.
+    async fn run_with(
+        hostname: Option<&str>,
+        args: &[&str],
+        limits: &Limits,
+        component: &[u8],
+        serve: impl Future<
+            Output = Result<(
+                impl Future<Output = Result<()>> + Unpin + Send + 'static,
+                SocketAddr,
+            )>,
+        >,
+    ) -> Result<Store<SocketsCtx>> {
+        let (_tx, address) = spawn_server(serve).await?;
+
+        let component = shared().component(component)?;
+
+        let mut store = store(wasi(hostname, address, args).build(), limits)?;
+
+        // Resolve `hostname` using a stub rather than the system resolver, since e.g. whether `localhost` maps to
+        // `::1`, `127.0.0.1`, or both varies from machine to machine.
+        let _resolver_tx = if let Some(hostname) = hostname {
+            let (tx, resolver) = spawn_server(serve_dns(
+                (Ipv4Addr::LOCALHOST, 0).into(),
+                HashMap::from([(
+                    hostname.to_owned(),
+                    DnsRecord {
+                        addresses: vec![address.ip()],
+                        ..DnsRecord::default()
+                    },
+                )]),
+            ))
+            .await?;
+            store.data_mut().resolver = Some(resolver);
+            Some(tx)
+        } else {
+            None
+        };
+
+        run(&mut store, &component).await?;
+
+        Ok(store)
+    }
+
+    /// Count the entries currently occupying `table`.
+    fn live_entries(table: &mut ResourceTable) -> usize {
+        // `ResourceTable` doesn't expose its length, so probe every key a test could plausibly have allocated.  Keys
+        // are indexes into the table, reusing freed slots first, and no test creates anywhere near this many
+        // resources in one store.
+        (0..=u32::from(u16::MAX))
+            .filter(|&key| table.get_any_mut(key).is_ok())
+            .count()
+    }
+
+    #[test]
+    fn live_entries_counts_occupied_slots() -> Result<()> {
+        let mut table = ResourceTable::new();
+        assert_eq!(0, live_entries(&mut table));
+
+        let first = table.push(1_u32)?;
+        let second = table.push(2_u32)?;
+        table.push(3_u32)?;
+        assert_eq!(3, live_entries(&mut table));
+
+        table.delete(first)?;
+        table.delete(second)?;
+        assert_eq!(1, live_entries(&mut table));
+
+        table.push(4_u32)?;
+        assert_eq!(2, live_entries(&mut table));
+
+        Ok(())
+    }
+
+    fn permutations<T: Copy>(items: &[T]) -> Vec<Vec<T>> {
+        if items.is_empty() {
+            return vec![Vec::new()];
+        }
+
+        (0..items.len())
+            .flat_map(|index| {
+                let mut rest = items.to_vec();
+                let item = rest.remove(index);
+                permutations(&rest).into_iter().map(move |mut tail| {
+                    tail.insert(0, item);
+                    tail
+                })
+            })
+            .collect()
+    }
+
+    /// Return the index of the first resource in `order` which would be dropped while it still has live
+    /// children, if any.
+    fn first_invalid_drop(order: &[&str]) -> Option<usize> {
+        let children = |name| match name {
+            "socket" => &["input", "output", "socket-pollable"] as &[_],
+            "input" => &["input-pollable"],
+            "output" => &["output-pollable"],
+            _ => &[],
+        };
+
+        order.iter().enumerate().find_map(|(index, name)| {
+            children(*name)
+                .iter()
+                .any(|child| !order[..index].contains(child))
+                .then_some(index)
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_drop_order() -> Result<()> {
+        let (_tx, address) = spawn_server(serve_echo((Ipv4Addr::LOCALHOST, 0).into())).await?;
+
+        let component =
+            shared().component(&build_component("../client", "sockets-client").await?)?;
+
+        for order in permutations(&[
+            "socket",
+            "input",
+            "output",
+            "input-pollable",
+            "output-pollable",
+            "socket-pollable",
+        ]) {
+            let order_arg = order.join(",");
+            let mut store = store(
+                wasi(None, address, &["drop-order", order_arg.as_str()]).build(),
+                &Limits::default(),
+            )?;
+
+            let result = run(&mut store, &component).await;
+            let live = live_entries(&mut store.data_mut().table);
+
+            if let Some(index) = first_invalid_drop(&order) {
+                let error = result
+                    .err()
+                    .ok_or_else(|| anyhow!("expected {order_arg} to trap"))?;
+
+                assert!(
+                    matches!(
+                        error.downcast_ref::<ResourceTableError>(),
+                        Some(ResourceTableError::HasChildren)
+                    ),
+                    "{order_arg}: {error:?}"
+                );
+
+                // Everything from the offending resource onward is still live, plus the guest's `network`.
+                assert_eq!(order.len() - index + 1, live, "{order_arg}");
+            } else {
+                result.with_context(|| order_arg.clone())?;
+                assert_eq!(0, live, "{order_arg}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run several scenarios back to back in a single instance, checking that nothing leaks from one to the next.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_sequence() -> Result<()> {
+        let (_tx, address) = spawn_server(serve_echo((Ipv4Addr::LOCALHOST, 0).into())).await?;
+
+        let component =
+            shared().component(&build_component("../client", "sockets-client").await?)?;
+
+        let mut args = Vec::new();
+        for _ in 0..10 {
+            args.extend(["echo", "--"]);
+        }
+        args.extend([
+            "drop-order",
+            "input-pollable,output-pollable,socket-pollable,input,output,socket",
+        ]);
+        for _ in 0..10 {
+            args.extend(["--", "echo"]);
+        }
+
+        let mut store = store(wasi(None, address, &args).build(), &Limits::default())?;
+
+        run(&mut store, &component).await?;
+
+        assert_eq!(0, live_entries(&mut store.data_mut().table));
+
+        Ok(())
+    }
+
+    fn dns_record(addresses: &[&str], delay: Duration) -> Result<DnsRecord> {
+        Ok(DnsRecord {
+            addresses: addresses
+                .iter()
+                .map(|address| address.parse())
+                .collect::<Result<_, _>>()?,
+            delay,
+        })
+    }
+
+    /// Run each of `cases` as a `[mode, name, expected]` scenario in a single guest instance, resolving names
+    /// using a stub resolver serving `records`.
+    async fn test_lookups(records: &[(&str, DnsRecord)], cases: &[[&str; 3]]) -> Result<()> {
+        let (_tx, resolver) = spawn_server(serve_dns(
+            (Ipv4Addr::LOCALHOST, 0).into(),
+            records
+                .iter()
+                .map(|(name, record)| (name.to_string(), record.clone()))
+                .collect(),
+        ))
+        .await?;
+
+        test_lookups_with(Some(resolver), cases).await
+    }
+
+    /// Run the `lookup` and `resolve` scenarios in `cases`, using the stub resolver at `resolver` if specified or
+    /// else `wasmtime-wasi`'s own `ip-name-lookup` implementation.
+    async fn test_lookups_with(resolver: Option<SocketAddr>, cases: &[[&str; 3]]) -> Result<()> {
+        let component =
+            shared().component(&build_component("../client", "sockets-client").await?)?;
 
-    async fn test_postgres(
-        src_path: &str,
-        name: &str,
-        address: SocketAddr,
-        hostname: Option<&str>,
-    ) -> Result<()> {
-        test(
-            hostname,
-            &build_component(src_path, name).await?,
-            async move { serve_postgres(address).await },
-        )
-        .await
+        let args = cases.join(&"--");
+
+        let address = resolver.unwrap_or_else(|| (Ipv4Addr::LOCALHOST, 0).into());
+        let mut store = store(wasi(None, address, &args).build(), &Limits::default())?;
+        store.data_mut().resolver = resolver;
+
+        run(&mut store, &component).await
     }
 
-    async fn test_echo(
-        src_path: &str,
-        name: &str,
-        address: SocketAddr,
-        hostname: Option<&str>,
-    ) -> Result<()> {
-        test(
-            hostname,
-            &build_component(src_path, name).await?,
-            async move { serve_echo(address).await },
+    /// Check name resolution ordering and failure cases against records served by the stub resolver.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_lookup() -> Result<()> {
+        test_lookups(
+            &[
+                (
+                    "multi.test",
+                    dns_record(&["127.0.0.1", "::1", "127.0.0.2", "::2"], Duration::ZERO)?,
+                ),
+                ("v4.test", dns_record(&["10.0.0.1"], Duration::ZERO)?),
+                ("v6.test", dns_record(&["fd00::1"], Duration::ZERO)?),
+                ("empty.test", dns_record(&[], Duration::ZERO)?),
+                (
+                    "slow.test",
+                    dns_record(&["127.0.0.1"], Duration::from_millis(500))?,
+                ),
+                (
+                    "stalled.test",
+                    dns_record(&["127.0.0.1"], Duration::from_secs(30))?,
+                ),
+            ],
+            &[
+                ["lookup", "multi.test", "::1,::2,127.0.0.1,127.0.0.2"],
+                ["lookup", "MULTI.test", "::1,::2,127.0.0.1,127.0.0.2"],
+                ["lookup", "v4.test", "10.0.0.1"],
+                ["lookup", "v6.test", "fd00::1"],
+                ["lookup", "empty.test", ""],
+                ["slow-lookup", "slow.test", "127.0.0.1"],
+                ["slow-lookup", "stalled.test", "temporary-resolver-failure"],
+                ["lookup", "missing.test", "name-unresolvable"],
+                ["lookup", "192.0.2.1", "192.0.2.1"],
+            ],
         )
         .await
     }
 
-    async fn test_python_echo(
-        src_paths: &[&str],
-        address: SocketAddr,
-        hostname: Option<&str>,
-    ) -> Result<()> {
-        test(
-            hostname,
-            &build_python_component(src_paths).await?,
-            async move { serve_echo(address).await },
+    /// Check how `wasmtime-wasi`'s own `ip-name-lookup` implementation validates bracketed, scoped, empty, and
+    /// malformed names, both directly and via the guest's `<host>:<port>` parsing.
+    ///
+    /// No stub resolver is configured here, so every case is either an IP literal or a name `wasmtime-wasi` rejects
+    /// before consulting the system resolver.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_hostname_validation() -> Result<()> {
+        test_lookups_with(
+            None,
+            &[
+                // IPv6 literals are accepted with or without brackets, but not with a port or scope ID.
+                ["lookup", "::1", "::1"],
+                ["lookup", "[::1]", "::1"],
+                ["lookup", "192.0.2.1", "192.0.2.1"],
+                ["lookup", "[::1]:80", "invalid-argument"],
+                ["lookup", "fe80::1%lo", "invalid-argument"],
+                ["lookup", "fe80::1%1", "invalid-argument"],
+                ["lookup", "", "invalid-argument"],
+                ["lookup", "bad name.test", "invalid-argument"],
+                ["resolve", "[::1]:80", "[::1]:80"],
+                ["resolve", "[2001:db8::1]:443", "[2001:db8::1]:443"],
+                ["resolve", ":80", "error"],
+                ["resolve", "[::1]:65536", "error"],
+            ],
         )
         .await
     }
 
-    async fn test_python_redis(
-        src_paths: &[&str],
-        address: SocketAddr,
-        hostname: Option<&str>,
-    ) -> Result<()> {
-        test(
-            hostname,
-            &build_python_component(src_paths).await?,
-            async move { serve_redis(address).await },
+    /// Check how the stub resolver in `harness.rs` handles Unicode and over-long names, and how the guest's
+    /// `<host>:<port>` parsing handles names it resolves.
+    ///
+    /// These cases exercise the stub's punycode conversion and DNS length checks rather than `wasmtime-wasi`'s, since
+    /// the latter hands any syntactically valid name straight to the system resolver.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_stub_resolver_names() -> Result<()> {
+        let label63 = "a".repeat(63);
+        let label64 = "a".repeat(64);
+        let long_name = [label63.as_str(); 4].join(".");
+        let name63 = format!("{label63}.test");
+        let name64 = format!("{label64}.test");
+
+        test_lookups(
+            &[
+                (
+                    "xn--bcher-kva.test",
+                    dns_record(&["2001:db8::b"], Duration::ZERO)?,
+                ),
+                (
+                    name63.as_str(),
+                    dns_record(&["192.0.2.63"], Duration::ZERO)?,
+                ),
+                ("v6.test", dns_record(&["fd00::1"], Duration::ZERO)?),
+            ],
+            &[
+                // Unicode names are converted to punycode before being sent to the resolver.
+                ["lookup", "bücher.test", "2001:db8::b"],
+                ["lookup", "BÜCHER.test", "2001:db8::b"],
+                ["lookup", "xn--bcher-kva.test", "2001:db8::b"],
+                // DNS limits labels to 63 octets and names to 253.
+                ["lookup", name63.as_str(), "192.0.2.63"],
+                ["lookup", name64.as_str(), "invalid-argument"],
+                ["lookup", long_name.as_str(), "invalid-argument"],
+                ["resolve", "bücher.test:80", "[2001:db8::b]:80"],
+                ["resolve", "v6.test:8080", "[fd00::1]:8080"],
+                ["resolve", "[v6.test]:8080", "error"],
+                ["resolve", "v6.test", "error"],
+                ["resolve", "v6.test:", "error"],
+            ],
         )
         .await
     }
 
-    async fn test(
-        hostname: Option<&str>,
+    /// Discover the guest's scenarios via `test:test/scenarios` and call each of `runs` by name in one instance,
+    /// returning the store afterward.
+    async fn test_scenarios(
         component: &[u8],
-        serve: impl Future<
-            Output = Result<(
-                impl Future<Output = Result<()>> + Unpin + Send + 'static,
-                SocketAddr,
-            )>,
-        >,
-    ) -> Result<()> {
-        static ONCE: Once = Once::new();
-        ONCE.call_once(pretty_env_logger::init);
+        runs: &[(&str, &[&str])],
+    ) -> Result<Store<SocketsCtx>> {
+        let (_tx, address) = spawn_server(serve_echo((Ipv4Addr::LOCALHOST, 0).into())).await?;
 
-        let (server, address) = serve.await?;
+        let component = shared().component(component)?;
 
-        let (_tx, rx) = oneshot::channel::<()>();
+        let mut store = store(wasi(None, address, &[]).build(), &Limits::default())?;
 
-        task::spawn(async move {
-            drop(future::select(server, rx).await);
-        });
+        let scenarios = instantiate_scenarios(&mut store, &component).await?;
+        let scenarios = scenarios.test_test_scenarios();
+
+        let names = scenarios.call_list_scenarios(&mut store).await?;
+        for (name, _) in runs {
+            assert!(
+                names.iter().any(|n| n == name),
+                "{name} missing from {names:?}"
+            );
+        }
 
-        let mut config = Config::new();
-        config.wasm_component_model(true);
-        config.async_support(true);
+        let config = |args: &[&str]| ScenarioConfig {
+            address: address.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        };
 
-        let engine = Engine::new(&config)?;
+        for (name, args) in runs {
+            scenarios
+                .call_run_scenario(&mut store, name, &config(args))
+                .await?
+                .map_err(|e| anyhow!("{name}: {e}"))?;
+        }
+
+        assert!(
+            scenarios
+                .call_run_scenario(&mut store, "no-such-scenario", &config(&[]))
+                .await?
+                .is_err()
+        );
 
-        let component = Component::new(&engine, component)?;
+        Ok(store)
+    }
 
-        let mut linker = Linker::new(&engine);
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_scenarios() -> Result<()> {
+        let mut store = test_scenarios(
+            &build_component("../client", "sockets-client").await?,
+            &[
+                ("echo", &[]),
+                (
+                    "drop-order",
+                    &["input-pollable,output-pollable,socket-pollable,input,output,socket"],
+                ),
+                ("echo", &[]),
+            ],
+        )
+        .await?;
 
-        wasmtime_wasi::add_to_linker_async(&mut linker)?;
+        assert_eq!(0, live_entries(&mut store.data_mut().table));
 
-        let table = ResourceTable::new();
-        let wasi = WasiCtxBuilder::new()
-            .inherit_stdio()
-            .inherit_network()
-            .allow_ip_name_lookup(true)
-            .arg("sockets-client")
-            .arg(
-                hostname
-                    .map(|h| format!("{h}:{}", address.port()))
-                    .unwrap_or_else(|| format!("{address}")),
-            )
-            .build();
+        Ok(())
+    }
 
-        let mut store = Store::new(&engine, SocketsCtx { table, wasi });
+    #[tokio::test(flavor = "multi_thread")]
+    async fn std_scenarios() -> Result<()> {
+        test_scenarios(
+            &build_component("../client-std", "sockets-client-std").await?,
+            &[("echo", &[]), ("bench", &["2", "1024"]), ("echo", &[])],
+        )
+        .await
+        .map(drop)
+    }
 
-        let command = bindings::Command::instantiate_async(&mut store, &component, &linker).await?;
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokio_scenarios() -> Result<()> {
+        test_scenarios(
+            &build_component("../client-tokio", "sockets-client-tokio").await?,
+            &[("echo", &[]), ("bench", &["2", "1024"]), ("echo", &[])],
+        )
+        .await
+        .map(drop)
+    }
 
-        command
-            .wasi_cli_run()
-            .call_run(&mut store)
-            .await?
-            .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
+    #[tokio::test(flavor = "multi_thread")]
+    async fn python_scenarios() -> Result<()> {
+        test_scenarios(
+            &build_python_component(&["../client-python"], "test:test/command").await?,
+            &[("echo", &[]), ("bench", &["2", "1024"]), ("echo", &[])],
+        )
+        .await
+        .map(drop)
     }
 
     #[tokio::test(flavor = "multi_thread")]
@@ -572,6 +3281,188 @@ mod tests {
         .await
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_socket_limit() -> Result<()> {
+        test_with(
+            None,
+            &["socket-limit", "4"],
+            &Limits {
+                max_sockets: 4,
+                ..Limits::default()
+            },
+            &build_component("../client", "sockets-client").await?,
+            serve_echo((Ipv4Addr::LOCALHOST, 0).into()),
+        )
+        .await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_epoch_deadline() -> Result<()> {
+        let error = test_with(
+            None,
+            &["spin"],
+            &Limits {
+                budget: Duration::from_secs(1),
+                ..Limits::default()
+            },
+            &build_component("../client", "sockets-client").await?,
+            serve_echo((Ipv4Addr::LOCALHOST, 0).into()),
+        )
+        .await
+        .err()
+        .ok_or_else(|| anyhow!("expected guest to be interrupted"))?;
+
+        assert_eq!(Some(&Trap::Interrupt), error.downcast_ref::<Trap>());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_memory_limit() -> Result<()> {
+        let error = test_with(
+            None,
+            &["allocate"],
+            &Limits {
+                memory_size: 16 * 1024 * 1024,
+                ..Limits::default()
+            },
+            &build_component("../client", "sockets-client").await?,
+            serve_echo((Ipv4Addr::LOCALHOST, 0).into()),
+        )
+        .await
+        .err()
+        .ok_or_else(|| anyhow!("expected guest to run out of memory"))?;
+
+        // Once `memory.grow` fails, the guest's allocator aborts, which traps with `unreachable`.  Anything else
+        // (e.g. an interrupt because the guest spent its whole budget) means the memory limit wasn't what stopped it.
+        let trap = error.downcast_ref::<Trap>();
+        assert_ne!(Some(&Trap::Interrupt), trap, "{error:?}");
+        assert_eq!(Some(&Trap::UnreachableCodeReached), trap, "{error:?}");
+
+        Ok(())
+    }
+
+    /// Check that `Limits::table_elements` caps how far a module can grow its tables.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn table_limit() -> Result<()> {
+        let module = Module::new(
+            &shared().engine,
+            r#"(module
+                 (table 0 funcref)
+                 (func (export "grow") (param i32) (result i32)
+                   (table.grow (ref.null func) (local.get 0))))"#,
+        )?;
+
+        let mut store = store(
+            wasi(None, (Ipv4Addr::LOCALHOST, 0).into(), &[]).build(),
+            &Limits {
+                table_elements: 1000,
+                ..Limits::default()
+            },
+        )?;
+
+        let instance = Instance::new_async(&mut store, &module, &[]).await?;
+        let grow = instance.get_typed_func::<u32, i32>(&mut store, "grow")?;
+
+        // `table.grow` returns the previous size on success and -1 on failure.
+        assert_eq!(0, grow.call_async(&mut store, 1000).await?);
+        assert_eq!(-1, grow.call_async(&mut store, 1).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_tcp_options_ipv4() -> Result<()> {
+        test_tcp_options((Ipv4Addr::LOCALHOST, 0).into()).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_tcp_options_ipv6() -> Result<()> {
+        test_tcp_options((Ipv6Addr::LOCALHOST, 0).into()).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_udp_options_ipv4() -> Result<()> {
+        test_udp_options((Ipv4Addr::LOCALHOST, 0).into()).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_udp_options_ipv6() -> Result<()> {
+        test_udp_options((Ipv6Addr::LOCALHOST, 0).into()).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_half_close_ipv4() -> Result<()> {
+        test_half_close((Ipv4Addr::LOCALHOST, 0).into()).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_half_close_ipv6() -> Result<()> {
+        test_half_close((Ipv6Addr::LOCALHOST, 0).into()).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_bind_ipv4() -> Result<()> {
+        test_bind((Ipv4Addr::LOCALHOST, 0).into()).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_bind_ipv6() -> Result<()> {
+        test_bind((Ipv6Addr::LOCALHOST, 0).into()).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_reactor() -> Result<()> {
+        test_with(
+            None,
+            &["reactor", "50"],
+            &Limits::default(),
+            &build_component("../client", "sockets-client").await?,
+            serve_echo((Ipv4Addr::LOCALHOST, 0).into()),
+        )
+        .await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_readiness_ipv4() -> Result<()> {
+        test_readiness((Ipv4Addr::LOCALHOST, 0).into()).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_readiness_ipv6() -> Result<()> {
+        test_readiness((Ipv6Addr::LOCALHOST, 0).into()).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn direct_dual_stack() -> Result<()> {
+        test_with(
+            None,
+            &["dual-stack"],
+            &Limits::default(),
+            &build_component("../client", "sockets-client").await?,
+            serve_dual_stack(0),
+        )
+        .await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "requires a link-local IPv6 address"]
+    async fn direct_scope_id() -> Result<()> {
+        test_scope_id("../client", "sockets-client").await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "requires a link-local IPv6 address"]
+    async fn std_scope_id() -> Result<()> {
+        test_scope_id("../client-std", "sockets-client-std").await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "requires a link-local IPv6 address"]
+    async fn tokio_scope_id() -> Result<()> {
+        test_scope_id("../client-tokio", "sockets-client-tokio").await
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn std_ipv4() -> Result<()> {
         test_echo(
@@ -605,6 +3496,26 @@ mod tests {
         .await
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokio_http_ipv4() -> Result<()> {
+        test_http((Ipv4Addr::LOCALHOST, 0).into()).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokio_http_ipv6() -> Result<()> {
+        test_http((Ipv6Addr::LOCALHOST, 0).into()).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokio_websocket_ipv4() -> Result<()> {
+        test_websocket((Ipv4Addr::LOCALHOST, 0).into()).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokio_websocket_ipv6() -> Result<()> {
+        test_websocket((Ipv6Addr::LOCALHOST, 0).into()).await
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn tokio_ipv4() -> Result<()> {
         test_echo(
@@ -645,6 +3556,7 @@ mod tests {
             "sockets-client-tokio-postgres",
             (Ipv6Addr::LOCALHOST, 0).into(),
             None,
+            &[],
         )
         .await
     }
@@ -656,6 +3568,85 @@ mod tests {
             "sockets-client-tokio-postgres",
             (Ipv6Addr::LOCALHOST, 0).into(),
             Some("localhost"),
+            &[],
+        )
+        .await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokio_postgres_copy() -> Result<()> {
+        test_postgres(
+            "../client-tokio-postgres",
+            "sockets-client-tokio-postgres",
+            (Ipv6Addr::LOCALHOST, 0).into(),
+            None,
+            &["copy"],
+        )
+        .await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokio_postgres_cancel() -> Result<()> {
+        test_postgres(
+            "../client-tokio-postgres",
+            "sockets-client-tokio-postgres",
+            (Ipv6Addr::LOCALHOST, 0).into(),
+            None,
+            &["cancel"],
+        )
+        .await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokio_postgres_transaction() -> Result<()> {
+        test_postgres(
+            "../client-tokio-postgres",
+            "sockets-client-tokio-postgres",
+            (Ipv6Addr::LOCALHOST, 0).into(),
+            None,
+            &["transaction"],
+        )
+        .await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokio_postgres_pool() -> Result<()> {
+        const POOL_SIZE: usize = 4;
+
+        let store = run_with(
+            None,
+            &["pool", &POOL_SIZE.to_string()],
+            &Limits::default(),
+            &build_component("../client-tokio-postgres", "sockets-client-tokio-postgres").await?,
+            serve_postgres((Ipv6Addr::LOCALHOST, 0).into()),
+        )
+        .await?;
+
+        // One socket for the guest's initial, unpooled connection; one for each connection in the pool; and one
+        // for each connection the pool had to replace after the server dropped the originals.
+        assert_eq!(1 + 2 * POOL_SIZE, store.data().sockets_created());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokio_redis() -> Result<()> {
+        test_redis(
+            "../client-tokio-redis",
+            "sockets-client-tokio-redis",
+            (Ipv6Addr::LOCALHOST, 0).into(),
+            None,
+        )
+        .await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokio_redis_name() -> Result<()> {
+        test_redis(
+            "../client-tokio-redis",
+            "sockets-client-tokio-redis",
+            (Ipv6Addr::LOCALHOST, 0).into(),
+            Some("localhost"),
         )
         .await
     }
@@ -686,6 +3677,18 @@ mod tests {
             &["../client-python-redis", "../client-python-redis/redis-py"],
             (Ipv6Addr::LOCALHOST, 0).into(),
             None,
+            &[],
+        )
+        .await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn python_redis_resp3() -> Result<()> {
+        test_python_redis(
+            &["../client-python-redis", "../client-python-redis/redis-py"],
+            (Ipv6Addr::LOCALHOST, 0).into(),
+            None,
+            &["resp3"],
         )
         .await
     }
@@ -696,7 +3699,50 @@ mod tests {
             &["../client-python-redis", "../client-python-redis/redis-py"],
             (Ipv6Addr::LOCALHOST, 0).into(),
             Some("localhost"),
+            &[],
+        )
+        .await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn python_redis_pubsub() -> Result<()> {
+        let broker = Arc::new(RedisBroker::default());
+
+        let publisher = task::spawn({
+            let broker = broker.clone();
+            async move {
+                broker.wait_for_subscribers(b"weather", 1).await;
+                for message in ["sunny", "windy", "rain"] {
+                    assert_eq!(1, broker.publish(b"weather", message.as_bytes()));
+                }
+            }
+        });
+
+        test_with(
+            None,
+            &["pubsub"],
+            &Limits::default(),
+            &build_python_component(
+                &["../client-python-redis", "../client-python-redis/redis-py"],
+                "wasi:cli/command@0.2.0",
+            )
+            .await?,
+            serve_redis_with((Ipv6Addr::LOCALHOST, 0).into(), broker.clone()),
         )
+        .await?;
+
+        publisher.await?;
+
+        // Give the fixture a moment to notice the disconnect, then make sure it cleaned up.
+        time::timeout(Duration::from_secs(5), async {
+            while ["news", "weather"]
+                .iter()
+                .any(|channel| broker.subscriber_count(channel.as_bytes()) > 0)
+            {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        })
         .await
+        .map_err(|_| anyhow!("subscriptions outlived the connection"))
     }
 }